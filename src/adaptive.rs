@@ -0,0 +1,128 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+//! Sorting functions that pick the fastest algorithm for the input,
+//! so that they can be used as a drop-in replacement for
+//! [`slice::sort_unstable`](https://doc.rust-lang.org/std/primitive.slice.html#method.sort_unstable)
+//! without ever being much slower than it.
+//!
+//! Short slices are sorted with `sort_unstable_by`.
+//! Longer slices are first checked for already being sorted.
+//! If not, they are sampled with [`precheck_by`](../fn.precheck_by.html), and then sorted with
+//! Drop-Merge sort if they look nearly sorted, or with `sort_unstable_by` otherwise.
+//!
+//! The thresholds come from benchmarking `i32`: below 256 elements `sort_unstable` was faster than Drop-Merge sort
+//! for every disorder, and than binary insertion sort for every length.
+//! Above that Drop-Merge sort was faster when less than about 5% (1k elements), 15% (10k elements)
+//! or 25% (more elements) of the elements were out of order.
+
+use std::cmp::Ordering;
+
+use config::DmSortConfig;
+use dmsort::try_sort_columns_move_by;
+use precheck::{precheck_by, Algorithm};
+use run_scan::NoRunScan;
+use sub_sorter::SortUnstable;
+
+// ----------------------------------------------------------------------------
+
+/// Slices shorter than this are sorted with `sort_unstable_by`.
+const DROP_MERGE_MIN_LEN: usize = 256;
+
+/// Slices estimated to have more than this fraction of their elements out of order are sorted with `sort_unstable_by`.
+/// Drop-Merge sort has less of an advantage on short slices.
+fn drop_merge_max_disorder(len: usize) -> f32 {
+    if len < 1_000 {
+        0.05
+    } else if len < 10_000 {
+        0.15
+    } else {
+        0.25
+    }
+}
+
+/// Sorts the elements using the given compare function, picking the algorithm based on the input.
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::adaptive::sort_by(&mut numbers, |a, b| b.cmp(a));
+/// assert_eq!(numbers, vec!(7, 6, 5, 4, 3, 2, 1, 0));
+/// ```
+pub fn sort_by<T, F>(slice: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    if slice.len() < DROP_MERGE_MIN_LEN {
+        slice.sort_unstable_by(compare);
+        return;
+    }
+
+    // Already sorted input is common, and cheap to check for.
+    // Otherwise Drop-Merge sort starts after the sorted prefix, so that it is only compared once:
+    let sorted_len = sorted_prefix_len(slice, &mut compare);
+    if sorted_len == slice.len() {
+        return;
+    }
+
+    let config = DmSortConfig {
+        precheck_max_disorder: drop_merge_max_disorder(slice.len()),
+        ..Default::default()
+    };
+    match precheck_by(slice, &config, &mut compare).algorithm {
+        Algorithm::Fallback => slice.sort_unstable_by(compare),
+        Algorithm::InsertionSort | Algorithm::DropMerge => {
+            let result = try_sort_columns_move_by(
+                slice,
+                (),
+                &config,
+                sorted_len,
+                None,
+                None,
+                NoRunScan,
+                &mut SortUnstable,
+                &mut SortUnstable,
+                compare,
+            );
+            debug_assert!(result.is_ok());
+        }
+    }
+}
+
+/// Sorts the elements using the given key function, picking the algorithm based on the input.
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::adaptive::sort_by_key(&mut numbers, |x| -x);
+/// assert_eq!(numbers, vec!(7, 6, 5, 4, 3, 2, 1, 0));
+/// ```
+pub fn sort_by_key<T, K, F>(slice: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    sort_by(slice, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Sorts the elements using the Ord trait, picking the algorithm based on the input.
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::adaptive::sort(&mut numbers);
+/// assert_eq!(numbers, vec!(0, 1, 2, 3, 4, 5, 6, 7));
+/// ```
+pub fn sort<T: Ord>(slice: &mut [T]) {
+    sort_by(slice, |a, b| a.cmp(b));
+}
+
+/// The length of the longest sorted prefix.
+fn sorted_prefix_len<T, F>(slice: &[T], compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    match slice
+        .windows(2)
+        .position(|pair| compare(&pair[1], &pair[0]) == Ordering::Less)
+    {
+        Some(i) => i + 1,
+        None => slice.len(),
+    }
+}
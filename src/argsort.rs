@@ -0,0 +1,272 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+
+use dmsort::sort_by;
+
+// ----------------------------------------------------------------------------
+
+/// An integer type that can be used as an index into a slice, e.g. in a permutation returned by [`argsort`](fn.argsort.html).
+pub trait PermutationIndex: Copy {
+    /// Panics if `index` does not fit.
+    fn from_usize(index: usize) -> Self;
+    fn to_usize(self) -> usize;
+}
+
+impl PermutationIndex for usize {
+    #[inline(always)]
+    fn from_usize(index: usize) -> Self {
+        index
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        self
+    }
+}
+
+impl PermutationIndex for u32 {
+    #[inline(always)]
+    fn from_usize(index: usize) -> Self {
+        assert!(
+            index <= u32::MAX as usize,
+            "Index {} does not fit in a u32",
+            index
+        );
+        index as u32
+    }
+
+    #[inline(always)]
+    fn to_usize(self) -> usize {
+        self as usize
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Something that can be reordered by swapping elements, e.g. a slice or a tuple of slices of the same length.
+pub trait Permutable {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn swap(&mut self, a: usize, b: usize);
+}
+
+impl<T> Permutable for &mut [T] {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    #[inline(always)]
+    fn swap(&mut self, a: usize, b: usize) {
+        <[T]>::swap(self, a, b);
+    }
+}
+
+impl<T> Permutable for &mut Vec<T> {
+    #[inline(always)]
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    #[inline(always)]
+    fn swap(&mut self, a: usize, b: usize) {
+        <[T]>::swap(self, a, b);
+    }
+}
+
+macro_rules! impl_permutable_for_tuple {
+    ($($name:ident : $index:tt),+) => {
+        impl<$($name: Permutable),+> Permutable for ($($name,)+) {
+            #[inline(always)]
+            fn len(&self) -> usize {
+                let len = (self.0).len();
+                $(assert_eq!((self.$index).len(), len, "All columns must have the same length");)+
+                len
+            }
+
+            #[inline(always)]
+            fn swap(&mut self, a: usize, b: usize) {
+                $((self.$index).swap(a, b);)+
+            }
+        }
+    };
+}
+
+impl_permutable_for_tuple!(A: 0);
+impl_permutable_for_tuple!(A: 0, B: 1);
+impl_permutable_for_tuple!(A: 0, B: 1, C: 2);
+impl_permutable_for_tuple!(A: 0, B: 1, C: 2, D: 3);
+impl_permutable_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_permutable_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+
+// ----------------------------------------------------------------------------
+
+fn argsort_indices_by<T, I, F>(slice: &[T], mut compare: F) -> Vec<I>
+where
+    I: PermutationIndex,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut indices: Vec<I> = (0..slice.len()).map(I::from_usize).collect();
+    sort_by(&mut indices, |a, b| {
+        compare(&slice[a.to_usize()], &slice[b.to_usize()])
+    });
+    indices
+}
+
+/// Returns the permutation that sorts the slice using the given compare function, without moving any elements.
+///
+/// The sorted order is `perm.iter().map(|&i| &slice[i])`.
+/// This runs Drop-Merge sort over the indices, so it performs `O(N + K * log(K))` comparisons.
+///
+/// # Examples
+/// ```
+/// let numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// let perm = dmsort::argsort_by(&numbers, |a, b| b.cmp(a));
+/// assert_eq!(perm, vec!(3, 2, 7, 6, 5, 4, 1, 0));
+/// ```
+pub fn argsort_by<T, F>(slice: &[T], compare: F) -> Vec<usize>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    argsort_indices_by(slice, compare)
+}
+
+/// Returns the permutation that sorts the slice using the given key function.
+///
+/// See [`argsort_by`](fn.argsort_by.html).
+///
+/// # Examples
+/// ```
+/// let numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// let perm = dmsort::argsort_by_key(&numbers, |x| -x);
+/// assert_eq!(perm, vec!(3, 2, 7, 6, 5, 4, 1, 0));
+/// ```
+pub fn argsort_by_key<T, K, F>(slice: &[T], mut key: F) -> Vec<usize>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    argsort_indices_by(slice, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Returns the permutation that sorts the slice using the Ord trait.
+///
+/// See [`argsort_by`](fn.argsort_by.html).
+///
+/// # Examples
+/// ```
+/// let numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// assert_eq!(dmsort::argsort(&numbers), vec!(0, 1, 4, 5, 6, 7, 2, 3));
+/// ```
+pub fn argsort<T: Ord>(slice: &[T]) -> Vec<usize> {
+    argsort_indices_by(slice, |a, b| a.cmp(b))
+}
+
+/// Like [`argsort_by`](fn.argsort_by.html), but returns `u32` indices to save memory.
+///
+/// # Panics
+/// Panics if the slice has more than `u32::MAX + 1` elements.
+pub fn argsort_by_u32<T, F>(slice: &[T], compare: F) -> Vec<u32>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    argsort_indices_by(slice, compare)
+}
+
+/// Like [`argsort_by_key`](fn.argsort_by_key.html), but returns `u32` indices to save memory.
+///
+/// # Panics
+/// Panics if the slice has more than `u32::MAX + 1` elements.
+pub fn argsort_by_key_u32<T, K, F>(slice: &[T], mut key: F) -> Vec<u32>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    argsort_indices_by(slice, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Like [`argsort`](fn.argsort.html), but returns `u32` indices to save memory.
+///
+/// # Panics
+/// Panics if the slice has more than `u32::MAX + 1` elements.
+///
+/// # Examples
+/// ```
+/// let numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// assert_eq!(dmsort::argsort_u32(&numbers), vec!(0, 1, 4, 5, 6, 7, 2, 3));
+/// ```
+pub fn argsort_u32<T: Ord>(slice: &[T]) -> Vec<u32> {
+    argsort_indices_by(slice, |a, b| a.cmp(b))
+}
+
+// ----------------------------------------------------------------------------
+
+/// Reorders `columns` so that `columns[i]` becomes what was `columns[perm[i]]`.
+///
+/// `columns` can be a single slice or a tuple of slices, which are then all reordered the same way.
+/// The permutation is applied by walking its cycles, so each element is swapped into place once
+/// and the only extra memory used is one bit per element.
+///
+/// # Panics
+/// Panics if `perm` is not a permutation of `0..columns.len()`. This is checked before anything is moved.
+///
+/// # Examples
+/// ```
+/// let mut keys = vec!(2, 0, 1);
+/// let mut names = vec!("two", "zero", "one");
+///
+/// let perm = dmsort::argsort(&keys);
+/// dmsort::apply_permutation_in_place(&perm, (&mut keys, &mut names));
+///
+/// assert_eq!(keys, vec!(0, 1, 2));
+/// assert_eq!(names, vec!("zero", "one", "two"));
+/// ```
+pub fn apply_permutation_in_place<I, P>(perm: &[I], mut columns: P)
+where
+    I: PermutationIndex,
+    P: Permutable,
+{
+    let len = columns.len();
+    assert_eq!(perm.len(), len, "The permutation has the wrong length");
+
+    // Check that it is a permutation before we touch `columns`, so that we don't panic half-way through.
+    // A set bit means that the index has yet to be visited:
+    let mut unvisited = vec![0u64; len.div_ceil(64)];
+    for index in perm {
+        let i = index.to_usize();
+        assert!(i < len, "Not a permutation");
+        let (word, bit) = (i / 64, 1 << (i % 64));
+        assert!(unvisited[word] & bit == 0, "Not a permutation");
+        unvisited[word] |= bit;
+    }
+
+    let mut mark_visited = |i: usize| {
+        let (word, bit) = (i / 64, 1 << (i % 64));
+        let was_visited = unvisited[word] & bit == 0;
+        unvisited[word] &= !bit;
+        was_visited
+    };
+
+    for start in 0..len {
+        if mark_visited(start) {
+            continue;
+        }
+
+        // Walk the cycle, pulling each element into place:
+        let mut current = start;
+        loop {
+            let source = perm[current].to_usize();
+            if source == start {
+                break;
+            }
+            mark_visited(source);
+            columns.swap(current, source);
+            current = source;
+        }
+    }
+}
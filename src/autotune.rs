@@ -0,0 +1,147 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+use std::time::Instant;
+
+use config::DmSortConfig;
+use dmsort::sort_by_with_config;
+
+// ----------------------------------------------------------------------------
+
+/// How many times each configuration is timed. The fastest time counts.
+const REPETITIONS: usize = 3;
+
+/// Tries configurations one at a time, keeping the cheapest.
+struct Tuner<C, M> {
+    /// Measures the cost of sorting the samples with a configuration.
+    measure: M,
+    best: DmSortConfig,
+    best_cost: C,
+}
+
+impl<C: Ord, M: FnMut(&DmSortConfig) -> C> Tuner<C, M> {
+    fn new(mut measure: M) -> Self {
+        let best = DmSortConfig::default();
+        let best_cost = measure(&best);
+        Tuner {
+            measure,
+            best,
+            best_cost,
+        }
+    }
+
+    /// Keep `config` if it is cheaper than the best so far.
+    fn try_config(&mut self, config: DmSortConfig) {
+        let cost = (self.measure)(&config);
+        if cost < self.best_cost {
+            self.best = config;
+            self.best_cost = cost;
+        }
+    }
+
+    /// Tune the parameters one at a time, starting from the default configuration.
+    fn tune(mut self) -> DmSortConfig {
+        for &recency in &[2, 4, 8, 16, 32] {
+            let config = DmSortConfig {
+                recency,
+                ..self.best
+            };
+            self.try_config(config);
+        }
+
+        for &early_out_disorder_fraction in &[0.4, 0.5, 0.6, 0.7, 0.8] {
+            let config = DmSortConfig {
+                early_out_disorder_fraction,
+                ..self.best
+            };
+            self.try_config(config);
+        }
+
+        for &precheck_max_disorder in &[0.1, 0.2, 0.3, 0.4] {
+            let config = DmSortConfig {
+                precheck: true,
+                precheck_max_disorder,
+                ..self.best
+            };
+            self.try_config(config);
+        }
+
+        self.best
+    }
+}
+
+/// Finds the fastest [`DmSortConfig`](struct.DmSortConfig.html) for sorting data like `samples`
+/// with the given compare function, by timing candidate configurations on them.
+///
+/// The parameters are tuned one at a time, starting from the default configuration.
+/// This sorts each sample a few hundred times, so use samples that are representative but not too big,
+/// and save the result with [`DmSortConfig::save`](struct.DmSortConfig.html#method.save)
+/// so it doesn't have to be done every time your program starts.
+///
+/// # Examples
+/// ```
+/// let samples : Vec<Vec<i32>> = (0..4).map(|i| (0..1000).map(|x| x ^ i).collect()).collect();
+/// let config = dmsort::autotune_by(&samples, |a, b| a.cmp(b));
+///
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::sort_by_with_config(&mut numbers, &config, |a, b| a.cmp(b));
+/// assert_eq!(numbers, vec!(0, 1, 2, 3, 4, 5, 6, 7));
+/// ```
+pub fn autotune_by<T, F>(samples: &[Vec<T>], mut compare: F) -> DmSortConfig
+where
+    T: Clone,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    Tuner::new(|config: &DmSortConfig| {
+        (0..REPETITIONS)
+            .map(|_| {
+                let mut samples = samples.to_vec();
+                let start = Instant::now();
+                for sample in &mut samples {
+                    sort_by_with_config(sample, config, |a, b| compare(a, b));
+                }
+                start.elapsed()
+            })
+            .min()
+            .unwrap_or_default()
+    })
+    .tune()
+}
+
+/// Finds the [`DmSortConfig`](struct.DmSortConfig.html) that needs the fewest comparisons for sorting `samples`.
+///
+/// Like [`autotune_by`](fn.autotune_by.html), but counts comparisons instead of timing.
+/// This is the better choice when comparing is what takes the time, such as for long strings,
+/// and it gives the same result every time.
+///
+/// # Examples
+/// ```
+/// let samples : Vec<Vec<i32>> = (0..4).map(|i| (0..1000).map(|x| x ^ i).collect()).collect();
+/// let config = dmsort::autotune_by_comparisons(&samples, |a, b| a.cmp(b));
+/// assert_eq!(config, dmsort::autotune_by_comparisons(&samples, |a, b| a.cmp(b)));
+/// ```
+pub fn autotune_by_comparisons<T, F>(samples: &[Vec<T>], mut compare: F) -> DmSortConfig
+where
+    T: Clone,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    Tuner::new(|config: &DmSortConfig| {
+        let mut num_comparisons = 0_usize;
+        for sample in samples {
+            let mut sample = sample.clone();
+            sort_by_with_config(&mut sample, config, |a, b| {
+                num_comparisons += 1;
+                compare(a, b)
+            });
+        }
+        num_comparisons
+    })
+    .tune()
+}
+
+/// Finds the fastest [`DmSortConfig`](struct.DmSortConfig.html) for sorting data like `samples`.
+///
+/// See [`autotune_by`](fn.autotune_by.html).
+pub fn autotune<T: Clone + Ord>(samples: &[Vec<T>]) -> DmSortConfig {
+    autotune_by(samples, |a, b| a.cmp(b))
+}
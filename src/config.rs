@@ -0,0 +1,240 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use dmsort::{EARLY_OUT, EARLY_OUT_DISORDER_FRACTION, EARLY_OUT_TEST_AT, RECENCY};
+
+// ----------------------------------------------------------------------------
+
+/// Tuning parameters for Drop-Merge sort, used by [`sort_by_with_config`](fn.sort_by_with_config.html).
+///
+/// The defaults are what [`sort_by`](fn.sort_by.html) uses.
+///
+/// # Examples
+/// ```
+/// let config = dmsort::DmSortConfig {
+///     early_out_disorder_fraction: 0.3,
+///     ..Default::default()
+/// };
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::sort_by_with_config(&mut numbers, &config, |a, b| a.cmp(b));
+/// assert_eq!(numbers, vec!(0, 1, 2, 3, 4, 5, 6, 7));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DmSortConfig {
+    /// How many elements in a row we drop before we backtrack, i.e. reconsider the last kept element.
+    /// Low values are faster when there is little disorder, high values are more resilient against
+    /// long stretches of noise. Must be at least one.
+    pub recency: usize,
+
+    /// Check how disordered the input is while scanning it,
+    /// and sort everything with the fallback instead if it is too disordered.
+    pub early_out: bool,
+
+    /// The first disorder check is done after reading this fraction of the input.
+    pub early_out_first_checkpoint: f32,
+
+    /// Each disorder check is done this many times further into the input than the previous one.
+    /// Must be greater than one.
+    pub early_out_checkpoint_growth: f32,
+
+    /// We early-out if more than this fraction of the elements read since the previous check were dropped.
+    pub early_out_disorder_fraction: f32,
+
+    /// Sample the input before sorting it to pick the algorithm, see [`precheck_by`](fn.precheck_by.html).
+    pub precheck: bool,
+
+    /// With `precheck`, slices up to this long are sorted with binary insertion sort.
+    pub insertion_sort_max_len: usize,
+
+    /// With `precheck`, slices estimated to have more than this fraction of their elements out of order
+    /// are sorted with the fallback right away.
+    pub precheck_max_disorder: f32,
+
+    /// The most out-of-order elements we keep in the extra buffer at once.
+    /// This bounds the extra memory to `max_dropped` elements, at the cost of speed when it is reached.
+    /// What then happens is decided by `max_dropped_policy`. Must be at least one.
+    pub max_dropped: usize,
+
+    /// What to do when there are `max_dropped` out-of-order elements.
+    pub max_dropped_policy: MaxDroppedPolicy,
+}
+
+/// What Drop-Merge sort does when it has dropped [`DmSortConfig::max_dropped`](struct.DmSortConfig.html#structfield.max_dropped) elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MaxDroppedPolicy {
+    /// Sort the dropped elements, merge them into the elements kept so far, and continue.
+    /// This is counted in `SortStats::num_flushes`.
+    ///
+    /// Each merge moves the kept elements, so with a small `max_dropped`
+    /// and a lot of disorder this is slow, until the work guard gives up and sorts everything with the fallback.
+    Merge,
+
+    /// Give up and sort everything in place with the fallback.
+    Fallback,
+}
+
+impl fmt::Display for MaxDroppedPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            MaxDroppedPolicy::Merge => "merge",
+            MaxDroppedPolicy::Fallback => "fallback",
+        })
+    }
+}
+
+impl FromStr for MaxDroppedPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(MaxDroppedPolicy::Merge),
+            "fallback" => Ok(MaxDroppedPolicy::Fallback),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Default for DmSortConfig {
+    fn default() -> Self {
+        DmSortConfig {
+            recency: RECENCY,
+            early_out: EARLY_OUT,
+            early_out_first_checkpoint: 1.0 / EARLY_OUT_TEST_AT as f32,
+            early_out_checkpoint_growth: 1.5,
+            early_out_disorder_fraction: EARLY_OUT_DISORDER_FRACTION,
+            precheck: false,
+            insertion_sort_max_len: 32,
+            precheck_max_disorder: 0.4,
+            max_dropped: usize::MAX,
+            max_dropped_policy: MaxDroppedPolicy::Merge,
+        }
+    }
+}
+
+impl DmSortConfig {
+    /// Saves the configuration to a small text file, e.g. after running [`autotune`](fn.autotune.html).
+    ///
+    /// # Examples
+    /// ```
+    /// let path = std::env::temp_dir().join("dmsort_config_example.txt");
+    /// let config = dmsort::DmSortConfig {
+    ///     recency: 4,
+    ///     ..Default::default()
+    /// };
+    /// config.save(&path).unwrap();
+    /// assert_eq!(dmsort::DmSortConfig::load(&path).unwrap(), config);
+    /// ```
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    /// Loads a configuration saved with [`save`](#method.save).
+    ///
+    /// Settings missing from the file get their default values, and unknown settings are ignored,
+    /// so files saved by other versions of this crate can still be loaded.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// The format used by [`DmSortConfig::save`](struct.DmSortConfig.html#method.save): one `name = value` per line.
+impl fmt::Display for DmSortConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "recency = {}", self.recency)?;
+        writeln!(f, "early_out = {}", self.early_out)?;
+        writeln!(
+            f,
+            "early_out_first_checkpoint = {}",
+            self.early_out_first_checkpoint
+        )?;
+        writeln!(
+            f,
+            "early_out_checkpoint_growth = {}",
+            self.early_out_checkpoint_growth
+        )?;
+        writeln!(
+            f,
+            "early_out_disorder_fraction = {}",
+            self.early_out_disorder_fraction
+        )?;
+        writeln!(f, "precheck = {}", self.precheck)?;
+        writeln!(
+            f,
+            "insertion_sort_max_len = {}",
+            self.insertion_sort_max_len
+        )?;
+        writeln!(f, "precheck_max_disorder = {}", self.precheck_max_disorder)?;
+        writeln!(f, "max_dropped = {}", self.max_dropped)?;
+        writeln!(f, "max_dropped_policy = {}", self.max_dropped_policy)
+    }
+}
+
+/// Returned when parsing a [`DmSortConfig`](struct.DmSortConfig.html) fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseConfigError {
+    line: String,
+}
+
+impl fmt::Display for ParseConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid dmsort config line: {:?}", self.line)
+    }
+}
+
+impl Error for ParseConfigError {}
+
+impl FromStr for DmSortConfig {
+    type Err = ParseConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn parse<V: FromStr>(value: &str, line: &str) -> Result<V, ParseConfigError> {
+            value.parse().map_err(|_| ParseConfigError {
+                line: line.to_owned(),
+            })
+        }
+
+        let mut config = DmSortConfig::default();
+        for line in s.lines() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            let (name, value) = match trimmed.split_once('=') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => {
+                    return Err(ParseConfigError {
+                        line: line.to_owned(),
+                    })
+                }
+            };
+            match name {
+                "recency" => config.recency = parse(value, line)?,
+                "early_out" => config.early_out = parse(value, line)?,
+                "early_out_first_checkpoint" => {
+                    config.early_out_first_checkpoint = parse(value, line)?
+                }
+                "early_out_checkpoint_growth" => {
+                    config.early_out_checkpoint_growth = parse(value, line)?
+                }
+                "early_out_disorder_fraction" => {
+                    config.early_out_disorder_fraction = parse(value, line)?
+                }
+                "precheck" => config.precheck = parse(value, line)?,
+                "insertion_sort_max_len" => config.insertion_sort_max_len = parse(value, line)?,
+                "precheck_max_disorder" => config.precheck_max_disorder = parse(value, line)?,
+                "max_dropped" => config.max_dropped = parse(value, line)?,
+                "max_dropped_policy" => config.max_dropped_policy = parse(value, line)?,
+                _ => {} // From another version of this crate.
+            }
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,184 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+use std::slice;
+
+use config::DmSortConfig;
+use dmsort::{find_lns, DmSorter, DropToBuffer};
+use run_scan::NoRunScan;
+use stats::SortStats;
+use sub_sorter::SortUnstable;
+
+// ----------------------------------------------------------------------------
+
+/// What the LNS scan and the merge do with an element that is equal to a kept one.
+pub(crate) trait Duplicates<T> {
+    /// Remove it? Otherwise it is kept like any other element that is in order.
+    const REMOVE: bool;
+
+    /// Called before `removed` is destroyed.
+    fn resolve(&mut self, removed: &mut T, survivor: &mut T);
+}
+
+/// Sort without removing anything.
+pub(crate) struct KeepDuplicates;
+
+impl<T> Duplicates<T> for KeepDuplicates {
+    const REMOVE: bool = false;
+
+    #[inline(always)]
+    fn resolve(&mut self, _removed: &mut T, _survivor: &mut T) {}
+}
+
+/// Remove duplicates, calling the wrapped function before each removal (see `sort_dedup_by`).
+pub(crate) struct RemoveDuplicates<R>(pub(crate) R);
+
+impl<T, R: FnMut(&mut T, &mut T)> Duplicates<T> for RemoveDuplicates<R> {
+    const REMOVE: bool = true;
+
+    #[inline(always)]
+    fn resolve(&mut self, removed: &mut T, survivor: &mut T) {
+        (self.0)(removed, survivor)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+// The Vec is empty while we sort, and the `DmSorter` owns the elements.
+//
+// Removing duplicates leaves holes in the gap of the `DmSorter`, which it closes up when done,
+// shortening its slice. We then give what is left back to the Vec - also if `compare` or `resolve` panics.
+//
+struct DedupSorter<'a, T: 'a> {
+    vec: &'a mut Vec<T>,
+    s: DmSorter<'a, T>,
+}
+
+impl<'a, T> Drop for DedupSorter<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            self.s.restore_dropped();
+            self.vec.set_len(self.s.slice.len());
+        }
+    }
+}
+
+/// Removes consecutive duplicates from an already sorted Vec.
+fn dedup_sorted<T, F, R>(vec: &mut Vec<T>, compare: &mut F, resolve: &mut R)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    R: FnMut(&mut T, &mut T),
+{
+    vec.dedup_by(|a, b| {
+        if compare(a, b) == Ordering::Equal {
+            resolve(a, b);
+            true
+        } else {
+            false
+        }
+    });
+}
+
+fn sort_dedup_move_by<T, F, R>(vec: &mut Vec<T>, mut compare: F, resolve: R)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    R: FnMut(&mut T, &mut T),
+{
+    let len = vec.len();
+    if len < 2 {
+        return;
+    }
+
+    let mut duplicates = RemoveDuplicates(resolve);
+
+    let finished = unsafe {
+        let slice = slice::from_raw_parts_mut(vec.as_mut_ptr(), len);
+        vec.set_len(0);
+        let mut d = DedupSorter {
+            vec: &mut *vec,
+            s: DmSorter::new(slice),
+        };
+
+        // The scan removes elements equal to the previously kept one,
+        // and the merge removes dropped elements equal to a kept one or to each other:
+        let finished = find_lns(
+            &mut d.s,
+            &DmSortConfig::default(),
+            &mut SortStats::default(),
+            NoRunScan,
+            &mut DropToBuffer,
+            &mut duplicates,
+            &mut SortUnstable,
+            &mut compare,
+        );
+        if finished {
+            d.s.sort_dropped_by(&mut SortUnstable, &mut compare);
+            d.s.merge_dropped_dedup(&mut duplicates, &mut compare);
+        }
+        finished
+    };
+
+    if !finished {
+        vec.sort_unstable_by(|a, b| compare(a, b));
+        dedup_sorted(vec, &mut compare, &mut duplicates.0);
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Sorts the elements using the given compare function and removes all but one of each run of equal elements.
+///
+/// Duplicates are removed while sorting, so this is cheaper than a `sort_by` followed by a `dedup_by`.
+///
+/// Before one of two equal elements is removed, `resolve(removed, survivor)` is called.
+/// This lets you choose which one survives (by swapping them) or combine them.
+///
+/// # Examples
+/// ```
+/// let mut pairs = vec!((0, 'a'), (1, 'b'), (3, 'c'), (2, 'd'), (1, 'e'));
+///
+/// // Keep the duplicate with the largest letter:
+/// dmsort::sort_dedup_by(&mut pairs, |a, b| a.0.cmp(&b.0), |removed, survivor| {
+///     if removed.1 > survivor.1 {
+///         std::mem::swap(removed, survivor);
+///     }
+/// });
+/// assert_eq!(pairs, vec!((0, 'a'), (1, 'e'), (2, 'd'), (3, 'c')));
+/// ```
+pub fn sort_dedup_by<T, F, R>(vec: &mut Vec<T>, compare: F, resolve: R)
+where
+    F: FnMut(&T, &T) -> Ordering,
+    R: FnMut(&mut T, &mut T),
+{
+    sort_dedup_move_by(vec, compare, resolve);
+}
+
+/// Sorts the elements using the given key function and removes all but one of each run of elements with equal keys.
+///
+/// Which of the duplicates survives is unspecified. Use [`sort_dedup_by`](fn.sort_dedup_by.html) to choose.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, -1, 6, 7, 2, 3, 4, 5, -5);
+/// dmsort::sort_dedup_by_key(&mut numbers, |x| x.abs());
+/// assert_eq!(numbers.len(), 8);
+/// ```
+pub fn sort_dedup_by_key<T, K, F>(vec: &mut Vec<T>, mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    sort_dedup_move_by(vec, |a, b| key(a).cmp(&key(b)), |_, _| {});
+}
+
+/// Sorts the elements using the Ord trait and removes consecutive duplicates.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 1, 4, 5, 7);
+/// dmsort::sort_dedup(&mut numbers);
+/// assert_eq!(numbers, vec!(0, 1, 2, 3, 4, 5, 6, 7));
+/// ```
+pub fn sort_dedup<T: Ord>(vec: &mut Vec<T>) {
+    sort_dedup_move_by(vec, |a, b| a.cmp(b), |_, _| {});
+}
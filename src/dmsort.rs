@@ -1,29 +1,76 @@
 // Copyright (c) 2017 Emil Ernerfeldt
 
-use std::cmp::Ordering;
+use std::cell::Cell;
+use std::cmp::{self, Ordering};
+use std::collections::TryReserveError;
+use std::mem;
 use std::ptr;
 
+use argsort::{apply_permutation_in_place, Permutable};
+use config::{DmSortConfig, MaxDroppedPolicy};
+use dedup::{Duplicates, KeepDuplicates};
+use dropped_buffer::DroppedBuffer;
+use precheck::{precheck_by, Algorithm};
+use run_scan::{NoRunScan, RunScan};
+use stats::{EarlyOut, SortStats};
+use sub_sorter::{InsertionSort, SortUnstable, SubSorter};
+use try_sort::AllocPolicy;
+use zip::SortColumns;
+
 // ----------------------------------------------------------------------------
 
 /// This speeds up well-ordered input by quite a lot.
-const DOUBLE_COMPARISONS: bool = true;
+pub(crate) const DOUBLE_COMPARISONS: bool = true;
 
 /// Low RECENCY = faster when there is low disorder (a lot of order).
 /// High RECENCY = more resilient against long stretches of noise.
 /// If RECENCY is too small we are more dependent on nice data/luck.
-const RECENCY: usize = 8;
+pub(crate) const RECENCY: usize = 8;
 
 /// Back-track several elements at once. This is helpful when there are big clumps out-of-order.
-const FAST_BACKTRACKING: bool = true;
+pub(crate) const FAST_BACKTRACKING: bool = true;
 
 /// Break early if we notice that the input is not ordered enough.
-const EARLY_OUT: bool = true;
+pub(crate) const EARLY_OUT: bool = true;
 
 /// Test for early-out when we have processed len / `EARLY_OUT_TEST_AT` elements.
-const EARLY_OUT_TEST_AT: usize = 4;
+pub(crate) const EARLY_OUT_TEST_AT: usize = 4;
 
 /// If more than this percentage of elements have been dropped, we abort.
-const EARLY_OUT_DISORDER_FRACTION: f32 = 0.60;
+pub(crate) const EARLY_OUT_DISORDER_FRACTION: f32 = 0.60;
+
+/// On early-out, keep the in-order elements found so far if they are at least this fraction of all elements.
+/// Otherwise everything is sorted with the fallback.
+pub(crate) const SALVAGE_MIN_FRACTION: f32 = 0.2;
+
+/// Abort the scan if it does too much work, so that adversarial input can't make us slower than the fallback.
+pub(crate) const WORK_GUARD: bool = true;
+
+/// The scan may do `WORK_BUDGET_FACTOR * len * log2(len)` units of work before the work guard aborts it.
+/// A unit of work is a handful of comparisons and moves. A normal scan does one or two units per element.
+pub(crate) const WORK_BUDGET_FACTOR: usize = 1;
+
+/// With a `RunScan`, look for a stretch of in-order elements once we have kept this many in a row.
+/// Looking after every kept element costs more than it saves when the input is disordered.
+pub(crate) const RUN_SCAN_MIN_KEPT: usize = 4;
+
+/// Use the branchless merge when there is at least one dropped element per this many kept ones.
+/// With longer runs of kept elements, galloping past them wins.
+const BRANCHLESS_MERGE_MAX_RUN: usize = 8;
+
+/// Small types without drop glue, which are cheap to copy speculatively.
+/// Larger types keep the galloping merge, which copies runs with `memmove`.
+fn is_branchless_mergeable<T>() -> bool {
+    !mem::needs_drop::<T>() && mem::size_of::<T>() <= 16
+}
+
+/// How much work the scan of `len` elements may do before the work guard aborts it.
+pub(crate) fn work_budget(len: usize) -> usize {
+    let log2_len = (usize::BITS - len.leading_zeros()) as usize;
+    WORK_BUDGET_FACTOR
+        .saturating_mul(len)
+        .saturating_mul(log2_len)
+}
 
 // ----------------------------------------------------------------------------
 
@@ -178,7 +225,6 @@ where
 pub fn sort_copy<T: Copy + Ord>(slice: &mut [T]) -> usize {
     sort_copy_by(slice, |a, b| a.cmp(b))
 }
-
 // ----------------------------------------------------------------------------
 
 // A note about protecting us from stack unwinding:
@@ -189,41 +235,453 @@ pub fn sort_copy<T: Copy + Ord>(slice: &mut [T]) -> usize {
 // This is done by temporarily bit-copying the data into the dropped vector
 // and copying them back if there is a panic.
 //
-struct DmSorter<'a, T: 'a> {
+pub(crate) struct DmSorter<'a, T: 'a, C: SortColumns = ()> {
     /// The slice we are sorting
-    slice: &'a mut [T],
+    pub(crate) slice: &'a mut [T],
 
-    /// Temporary storage of dropped elements.
-    dropped: Vec<T>,
+    /// Temporary storage of dropped elements. This is on the stack until there are too many of them.
+    pub(crate) dropped: DroppedBuffer<T>,
 
     /// Index in self.slice of where to write the next element to keep.
-    write: usize,
-    // slice[write..(write + dropped.len())] is a gap. The elements can be found in dropped
+    /// slice[write..(write + dropped.len() + num_holes)] is a gap. The elements can be found in dropped
+    pub(crate) write: usize,
+
+    /// How many duplicates have been removed from the gap (see `sort_dedup`).
+    num_holes: usize,
+
+    /// Companion columns that are moved in lockstep with self.slice (see `sort_zip`).
+    columns: C,
+
+    /// Temporary storage of the dropped elements of the companion columns.
+    columns_dropped: C::Dropped,
+
+    /// Grow `dropped` with `try_reserve` instead of `reserve`, so that we don't abort when out of memory.
+    fallible_alloc: bool,
+
+    /// Set when growing `dropped` failed. The scan is then aborted.
+    alloc_error: Option<TryReserveError>,
+
+    /// `dropped` never gets a bigger capacity than this (see `DmSortConfig::max_dropped`).
+    max_dropped: usize,
+
+    /// Set when the compare function has failed (see `try_sort_by`). The scan is then aborted.
+    interrupted: Option<&'a Cell<bool>>,
+}
+
+impl<'a, T> DmSorter<'a, T> {
+    pub(crate) fn new(slice: &'a mut [T]) -> Self {
+        DmSorter::with_columns(slice, ())
+    }
+}
+
+impl<'a, T, C: SortColumns> DmSorter<'a, T, C> {
+    pub(crate) fn with_columns(slice: &'a mut [T], mut columns: C) -> Self {
+        columns.assert_len(slice.len());
+        DmSorter {
+            slice,
+            dropped: DroppedBuffer::new(),
+            write: 0,
+            num_holes: 0,
+            columns,
+            columns_dropped: Default::default(),
+            fallible_alloc: false,
+            alloc_error: None,
+            max_dropped: usize::MAX,
+            interrupted: None,
+        }
+    }
+
+    /// Has the compare function failed, so that its answers no longer mean anything?
+    #[inline(always)]
+    fn is_interrupted(&self) -> bool {
+        match self.interrupted {
+            Some(interrupted) => interrupted.get(),
+            None => false,
+        }
+    }
+
+    /// slice[dest] = slice[source]
+    #[inline(always)]
+    unsafe fn copy(&mut self, source: usize, dest: usize) {
+        unsafe_copy(self.slice, source, dest);
+        self.columns.copy(source, dest);
+    }
+
+    /// slice[dest..(dest + count)] = slice[source..(source + count)]. The ranges may overlap.
+    #[inline(always)]
+    unsafe fn copy_block(&mut self, source: usize, dest: usize, count: usize) {
+        unsafe_copy_block(self.slice, source, dest, count);
+        self.columns.copy_block(source, dest, count);
+    }
+
+    /// Make room for `additional` more dropped elements.
+    #[cold]
+    fn reserve_dropped(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if self.max_dropped == usize::MAX {
+            if self.fallible_alloc {
+                self.dropped.try_reserve(additional)
+            } else {
+                self.dropped.reserve(additional);
+                Ok(())
+            }
+        } else {
+            // Grow like Vec does, but never beyond max_dropped:
+            let len = self.dropped.len();
+            let target = cmp::min(cmp::max(2 * self.dropped.capacity(), 8), self.max_dropped);
+            let additional = cmp::max(target, len + additional) - len;
+            if self.fallible_alloc {
+                self.dropped.try_reserve_exact(additional)
+            } else {
+                self.dropped.reserve_exact(additional);
+                Ok(())
+            }
+        }
+    }
+
+    /// Bit-copy slice[index] to the end of the dropped elements.
+    #[inline(always)]
+    unsafe fn push_dropped(&mut self, index: usize) -> Result<(), TryReserveError> {
+        if self.dropped.len() == self.dropped.capacity() {
+            self.reserve_dropped(1)?;
+        }
+        let old_len = self.dropped.len();
+        ptr::copy_nonoverlapping(
+            self.slice.get_unchecked(index),
+            self.dropped.as_mut_ptr().add(old_len),
+            1,
+        );
+        self.dropped.set_len(old_len + 1);
+        self.columns.push_dropped(&mut self.columns_dropped, index);
+        Ok(())
+    }
+
+    /// Bit-copy slice[index..(index + count)] to the end of the dropped elements.
+    #[inline(always)]
+    unsafe fn append_dropped(&mut self, index: usize, count: usize) -> Result<(), TryReserveError> {
+        let old_len = self.dropped.len();
+        if self.dropped.capacity() - old_len < count {
+            self.reserve_dropped(count)?;
+        }
+        ptr::copy_nonoverlapping(
+            self.slice.as_ptr().add(index),
+            self.dropped.as_mut_ptr().add(old_len),
+            count,
+        );
+        self.dropped.set_len(old_len + count);
+        self.columns
+            .append_dropped(&mut self.columns_dropped, index, count);
+        Ok(())
+    }
+
+    /// Forget the dropped elements after the first `len`. They must still be in the slice.
+    #[inline(always)]
+    unsafe fn truncate_dropped(&mut self, len: usize) {
+        self.dropped.set_len(len);
+        C::truncate_dropped(&mut self.columns_dropped, len);
+    }
+
+    /// Move the last dropped element to slice[dest].
+    #[inline(always)]
+    unsafe fn pop_dropped(&mut self, dest: usize) {
+        let new_len = self.dropped.len() - 1;
+        ptr::copy_nonoverlapping(
+            self.dropped.get_unchecked(new_len),
+            self.slice.get_unchecked_mut(dest),
+            1,
+        );
+        self.dropped.set_len(new_len);
+        self.columns.pop_dropped(&mut self.columns_dropped, dest);
+    }
+
+    /// Move back all dropped elements into the gap at slice[write..].
+    /// If duplicates were removed, the rest of the gap is closed up and the slice shortened.
+    pub(crate) unsafe fn restore_dropped(&mut self) {
+        let num_dropped = self.dropped.len();
+        ptr::copy_nonoverlapping(
+            self.dropped.as_ptr(),
+            self.slice.as_mut_ptr().add(self.write),
+            num_dropped,
+        );
+
+        // Make sure the objects aren't destroyed when self.dropped is dropped (avoid-double-free).
+        self.dropped.set_len(0);
+
+        self.columns
+            .restore_dropped(&mut self.columns_dropped, self.write);
+
+        if self.num_holes > 0 {
+            self.close_holes(self.write + num_dropped);
+        }
+    }
+
+    /// Move the elements after the gap down to `end`, and shorten the slice by the number of holes.
+    #[cold]
+    unsafe fn close_holes(&mut self, end: usize) {
+        let tail = end + self.num_holes;
+        let new_len = self.slice.len() - self.num_holes;
+        self.copy_block(tail, end, self.slice.len() - tail);
+        self.slice = &mut mem::take(&mut self.slice)[..new_len];
+        self.num_holes = 0;
+    }
+
+    /// Remove slice[index], which is equal to slice[survivor], leaving a hole in the gap.
+    #[inline(always)]
+    unsafe fn remove_duplicate<H>(&mut self, index: usize, survivor: usize, duplicates: &mut H)
+    where
+        H: Duplicates<T>,
+    {
+        let ptr = self.slice.as_mut_ptr();
+        duplicates.resolve(&mut *ptr.add(index), &mut *ptr.add(survivor));
+        self.num_holes += 1;
+        ptr::drop_in_place(ptr.add(index));
+    }
+
+    /// Sort the dropped elements, keeping the companion columns in lockstep.
+    /// With companion columns we sort a permutation, and the sub-sorter is not used.
+    pub(crate) fn sort_dropped_by<D, F>(&mut self, dropped_sorter: &mut D, compare: &mut F)
+    where
+        D: SubSorter<T>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        if C::NUM_COLUMNS == 0 {
+            dropped_sorter.sort_by(&mut self.dropped, |a, b| compare(a, b));
+        } else {
+            let perm = sort_permutation_by(&self.dropped, compare);
+            apply_permutation_in_place(
+                &perm,
+                (
+                    &mut self.dropped[..],
+                    PermutableDropped::<C>(&mut self.columns_dropped),
+                ),
+            );
+        }
+    }
+
+    /// Merge the sorted dropped elements into the sorted `slice[..write]`, from the back.
+    /// The gap `slice[write..(write + dropped.len())]` is filled, and `write` is moved past it.
+    /// Returns how many kept elements were moved.
+    ///
+    /// For each dropped element we gallop to find the kept elements that go after it,
+    /// and move them all at once. This takes O(K * log(N / K)) comparisons instead of O(N).
+    unsafe fn merge_dropped<F>(&mut self, compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        if C::NUM_COLUMNS == 0
+            && is_branchless_mergeable::<T>()
+            && self.dropped.len() * BRANCHLESS_MERGE_MAX_RUN >= self.write
+        {
+            return self.merge_dropped_branchless(compare);
+        }
+
+        // slice[write..back] is the gap the dropped elements go into.
+        let end = self.write + self.dropped.len();
+        let mut back = end;
+        let mut num_moved = 0;
+
+        while let Some(last_dropped) = self.dropped.last() {
+            let last_dropped: *const T = last_dropped;
+            let num_greater = gallop_from_back(&self.slice[..self.write], &*last_dropped, compare);
+            if num_greater > 0 {
+                self.copy_block(self.write - num_greater, back - num_greater, num_greater);
+                back -= num_greater;
+                self.write -= num_greater;
+                num_moved += num_greater;
+            }
+            self.pop_dropped(back - 1);
+            back -= 1;
+        }
+
+        self.write = end;
+        num_moved
+    }
+
+    /// Like `merge_dropped`, but one element at a time, picking it without a branch.
+    /// Used for small plain-data types when the dropped elements are dense,
+    /// where the galloping merge mispredicts on almost every element.
+    unsafe fn merge_dropped_branchless<F>(&mut self, compare: &mut F) -> usize
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let end = self.write + self.dropped.len();
+        let slice = self.slice.as_mut_ptr();
+        let dropped = self.dropped.as_ptr();
+        let num_kept_before = self.write;
+        let mut num_kept = num_kept_before;
+        let mut num_dropped = self.dropped.len();
+
+        // The gap is slice[num_kept..(num_kept + num_dropped)], and we keep `self` up to date
+        // so that a panic in `compare` leaves it where `Drop` expects it.
+        while num_kept > 0 && num_dropped > 0 {
+            let last_kept = slice.add(num_kept - 1);
+            let last_dropped = dropped.add(num_dropped - 1);
+            let take_kept = compare(&*last_dropped, &*last_kept) == Ordering::Less;
+            let source = if take_kept { last_kept } else { last_dropped };
+            ptr::copy_nonoverlapping(source, slice.add(num_kept + num_dropped - 1), 1);
+            num_kept -= take_kept as usize;
+            num_dropped -= !take_kept as usize;
+            self.write = num_kept;
+            self.dropped.set_len(num_dropped);
+        }
+
+        // Any dropped elements left are smaller than all kept ones:
+        self.restore_dropped();
+        self.write = end;
+        num_kept_before - num_kept
+    }
+
+    /// Like `merge_dropped`, but removes the dropped elements that are equal to a kept one or to each other.
+    /// The merged elements are then moved down to close the holes, which all end up after `slice[..write]`.
+    /// Returns how many kept elements were moved.
+    pub(crate) unsafe fn merge_dropped_dedup<H, F>(
+        &mut self,
+        duplicates: &mut H,
+        compare: &mut F,
+    ) -> usize
+    where
+        H: Duplicates<T>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        debug_assert_eq!(C::NUM_COLUMNS, 0);
+
+        // slice[write..back] is the gap the dropped elements go into, with room to spare for each hole.
+        let end = self.write + self.dropped.len() + self.num_holes;
+        let mut back = end;
+        let mut num_moved = 0;
+        let mut back_is_dropped = false; // Is slice[back] a merged dropped element?
+
+        while let Some(last_dropped) = self.dropped.last_mut() {
+            let last_dropped: *mut T = last_dropped;
+
+            // The dropped elements are sorted, so a duplicate of one is next to it:
+            let survivor = if back_is_dropped
+                && compare(&*last_dropped, self.slice.get_unchecked(back)) == Ordering::Equal
+            {
+                Some(back)
+            } else {
+                let num_greater =
+                    gallop_from_back(&self.slice[..self.write], &*last_dropped, compare);
+                if num_greater > 0 {
+                    self.copy_block(self.write - num_greater, back - num_greater, num_greater);
+                    back -= num_greater;
+                    self.write -= num_greater;
+                    num_moved += num_greater;
+                    back_is_dropped = false;
+                }
+                if self.write > 0
+                    && compare(&*last_dropped, self.slice.get_unchecked(self.write - 1))
+                        == Ordering::Equal
+                {
+                    Some(self.write - 1)
+                } else {
+                    None
+                }
+            };
+
+            if let Some(survivor) = survivor {
+                duplicates.resolve(&mut *last_dropped, self.slice.get_unchecked_mut(survivor));
+                let new_len = self.dropped.len() - 1;
+                self.dropped.set_len(new_len);
+                self.num_holes += 1;
+                ptr::drop_in_place(last_dropped);
+            } else {
+                self.pop_dropped(back - 1);
+                back -= 1;
+                back_is_dropped = true;
+            }
+        }
+
+        let num_merged = end - back;
+        self.copy_block(back, self.write, num_merged);
+        self.write += num_merged;
+        self.num_holes = end - self.write;
+        num_moved
+    }
+
+    /// Sort `slice[start..]` with the fallback sorting algorithm, keeping the companion columns in lockstep.
+    /// Must only be called when there are no dropped elements.
+    fn sort_tail_by<B, F>(&mut self, start: usize, fallback_sorter: &mut B, compare: &mut F)
+    where
+        B: SubSorter<T>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        debug_assert!(self.dropped.is_empty());
+        if C::NUM_COLUMNS == 0 {
+            fallback_sorter.sort_by(&mut self.slice[start..], |a, b| compare(a, b));
+        } else {
+            let perm = sort_permutation_by(&self.slice[start..], compare);
+            let len = self.slice.len() - start;
+            apply_permutation_in_place(
+                &perm,
+                (
+                    &mut self.slice[start..],
+                    PermutableColumns {
+                        columns: &mut self.columns,
+                        offset: start,
+                        len,
+                    },
+                ),
+            );
+        }
+    }
 }
 
-impl<'a, T> Drop for DmSorter<'a, T> {
+impl<'a, T, C: SortColumns> Drop for DmSorter<'a, T, C> {
     fn drop(&mut self) {
-        if self.dropped.is_empty() {
+        if self.dropped.is_empty() && self.num_holes == 0 {
             return;
         }
         unsafe {
-            // This code will only run on stack-unwind (panic).
+            // This code will only run on stack-unwind (panic), or when the compare function failed.
+            self.restore_dropped();
+        }
+    }
+}
 
-            // Move back all elements into the slice:
-            ptr::copy_nonoverlapping(
-                self.dropped.as_ptr(),
-                self.slice.as_mut_ptr().add(self.write),
-                self.dropped.len(),
-            );
+/// The permutation that sorts the slice with the fallback sorting algorithm.
+fn sort_permutation_by<T, F>(slice: &[T], compare: &mut F) -> Vec<usize>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut perm: Vec<usize> = (0..slice.len()).collect();
+    perm.sort_unstable_by(|&a, &b| unsafe {
+        compare(slice.get_unchecked(a), slice.get_unchecked(b))
+    });
+    perm
+}
 
-            // Make sure the objects aren't destroyed when self.dropped is dropped (avoid-double-free).
-            self.dropped.set_len(0);
-        }
+/// Lets us permute `offset..(offset + len)` of the companion columns.
+struct PermutableColumns<'b, C: 'b> {
+    columns: &'b mut C,
+    offset: usize,
+    len: usize,
+}
+
+impl<'b, C: SortColumns> Permutable for PermutableColumns<'b, C> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.columns.swap(self.offset + a, self.offset + b);
+    }
+}
+
+/// Lets us permute the dropped elements of the companion columns.
+struct PermutableDropped<'b, C: SortColumns + 'b>(&'b mut C::Dropped);
+
+impl<'b, C: SortColumns> Permutable for PermutableDropped<'b, C> {
+    fn len(&self) -> usize {
+        C::dropped_len(self.0)
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        C::swap_dropped(self.0, a, b);
     }
 }
 
 #[inline(always)]
-unsafe fn unsafe_push<T>(vec: &mut Vec<T>, value: &T) {
+pub(crate) unsafe fn unsafe_push<T>(vec: &mut Vec<T>, value: &T) {
     let old_len = vec.len();
     vec.reserve(1);
     ptr::copy_nonoverlapping(value, vec.as_mut_ptr().add(old_len), 1);
@@ -231,156 +689,609 @@ unsafe fn unsafe_push<T>(vec: &mut Vec<T>, value: &T) {
 }
 
 #[inline(always)]
-unsafe fn unsafe_copy<T>(slice: &mut [T], source: usize, dest: usize) {
+pub(crate) unsafe fn unsafe_copy<T>(slice: &mut [T], source: usize, dest: usize) {
     let ptr = slice.as_mut_ptr();
     ptr::copy_nonoverlapping(ptr.add(source), ptr.add(dest), 1);
 }
 
-fn sort_move_by<T, F>(slice: &mut [T], mut compare: F)
+#[inline(always)]
+pub(crate) unsafe fn unsafe_copy_block<T>(
+    slice: &mut [T],
+    source: usize,
+    dest: usize,
+    count: usize,
+) {
+    let ptr = slice.as_mut_ptr();
+    ptr::copy(ptr.add(source), ptr.add(dest), count);
+}
+
+/// Returns how many elements at the end of the sorted `slice` are greater than `element`.
+///
+/// This gallops (searches exponentially) from the back and then does a binary search,
+/// so it needs `O(log(n))` comparisons when the answer is `n`.
+#[inline]
+pub(crate) fn gallop_from_back<T, F>(slice: &[T], element: &T, compare: &mut F) -> usize
 where
     F: FnMut(&T, &T) -> Ordering,
 {
-    unsafe {
-        if slice.len() < 2 {
-            return;
+    let len = slice.len();
+
+    // The answer is in lo..hi:
+    let mut lo = 0;
+    let mut hi = 1;
+    while hi <= len && compare(element, &slice[len - hi]) == Ordering::Less {
+        lo = hi;
+        hi *= 2;
+    }
+    let hi = hi.min(len + 1);
+
+    let (start, end) = (len + 1 - hi, len - lo);
+    let num_not_greater =
+        slice[start..end].partition_point(|x| compare(element, x) != Ordering::Less);
+    len - (start + num_not_greater)
+}
+
+/// Where the LNS scan puts the elements it drops.
+/// They are in, or belong in, the gap `s.slice[s.write..(s.write + num_dropped)]`.
+pub(crate) trait DroppedStorage<T, C: SortColumns> {
+    /// Are the dropped elements left in the gap? Duplicates can then not be removed, since that leaves holes in it.
+    const IN_PLACE: bool;
+
+    fn num_dropped(&self, s: &DmSorter<T, C>) -> usize;
+
+    /// Keep `s.slice[source]` by moving it to `s.slice[dest]`, which is the start of the gap or was just dropped.
+    unsafe fn keep(&mut self, s: &mut DmSorter<T, C>, source: usize, dest: usize);
+
+    /// Keep `s.slice[source..(source + count)]` by moving it to the start of the gap at `dest`.
+    unsafe fn keep_block(
+        &mut self,
+        s: &mut DmSorter<T, C>,
+        source: usize,
+        dest: usize,
+        count: usize,
+    );
+
+    /// Drop `s.slice[index]`, which is either right after the gap or is overwritten with `keep` next.
+    unsafe fn push(&mut self, s: &mut DmSorter<T, C>, index: usize) -> Result<(), TryReserveError>;
+
+    /// Drop `s.slice[index..(index + count)]`, which is right before the gap (`s.write` has been moved back to `index`).
+    unsafe fn append(
+        &mut self,
+        s: &mut DmSorter<T, C>,
+        index: usize,
+        count: usize,
+    ) -> Result<(), TryReserveError>;
+
+    /// Un-drop all but the first `len` dropped elements. They must still be in the slice, right after the new gap.
+    unsafe fn truncate(&mut self, s: &mut DmSorter<T, C>, len: usize);
+
+    /// Put all dropped elements back into the gap, so that nothing is dropped.
+    unsafe fn restore(&mut self, s: &mut DmSorter<T, C>);
+
+    /// Sort the dropped elements with `dropped_sorter` and merge them into `s.slice[..s.write]`,
+    /// after which `s.write` is past them and nothing is dropped. Returns how many kept elements were moved.
+    unsafe fn flush<H, D, F>(
+        &mut self,
+        s: &mut DmSorter<T, C>,
+        duplicates: &mut H,
+        dropped_sorter: &mut D,
+        compare: &mut F,
+    ) -> usize
+    where
+        H: Duplicates<T>,
+        D: SubSorter<T>,
+        F: FnMut(&T, &T) -> Ordering;
+}
+
+/// Move the dropped elements out of the slice into `s.dropped`.
+/// This leaves the gap empty, so keeping an element is a single copy.
+pub(crate) struct DropToBuffer;
+
+impl<T, C: SortColumns> DroppedStorage<T, C> for DropToBuffer {
+    const IN_PLACE: bool = false;
+
+    #[inline(always)]
+    fn num_dropped(&self, s: &DmSorter<T, C>) -> usize {
+        s.dropped.len()
+    }
+
+    #[inline(always)]
+    unsafe fn keep(&mut self, s: &mut DmSorter<T, C>, source: usize, dest: usize) {
+        s.copy(source, dest);
+    }
+
+    #[inline(always)]
+    unsafe fn keep_block(
+        &mut self,
+        s: &mut DmSorter<T, C>,
+        source: usize,
+        dest: usize,
+        count: usize,
+    ) {
+        s.copy_block(source, dest, count);
+    }
+
+    #[inline(always)]
+    unsafe fn push(&mut self, s: &mut DmSorter<T, C>, index: usize) -> Result<(), TryReserveError> {
+        s.push_dropped(index)
+    }
+
+    #[inline(always)]
+    unsafe fn append(
+        &mut self,
+        s: &mut DmSorter<T, C>,
+        index: usize,
+        count: usize,
+    ) -> Result<(), TryReserveError> {
+        s.append_dropped(index, count)
+    }
+
+    #[inline(always)]
+    unsafe fn truncate(&mut self, s: &mut DmSorter<T, C>, len: usize) {
+        s.truncate_dropped(len);
+    }
+
+    unsafe fn restore(&mut self, s: &mut DmSorter<T, C>) {
+        s.restore_dropped();
+    }
+
+    unsafe fn flush<H, D, F>(
+        &mut self,
+        s: &mut DmSorter<T, C>,
+        duplicates: &mut H,
+        dropped_sorter: &mut D,
+        compare: &mut F,
+    ) -> usize
+    where
+        H: Duplicates<T>,
+        D: SubSorter<T>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        s.sort_dropped_by(dropped_sorter, compare);
+        if H::REMOVE {
+            s.merge_dropped_dedup(duplicates, compare)
+        } else {
+            s.merge_dropped(compare)
         }
+    }
+}
 
-        let mut s = DmSorter {
-            slice,
-            dropped: Vec::new(),
-            write: 0,
-        };
+/// First step of Drop-Merge sort: heuristically find the Longest Nondecreasing Subsequence (LNS).
+///
+/// The LNS is shifted into `s.slice[..s.write]` while the elements not part of it are dropped into `storage`.
+/// We start after `s.slice[..s.write]`, which must already be in order.
+/// Returns `false` if the input was too disordered to be worth it, in which case we abort early.
+/// All elements are then back in `s.slice` (in some unspecified order) and nothing is dropped.
+/// The reason is recorded in `stats.early_out`.
+///
+/// With `config.early_out` we check the disorder at geometrically spaced checkpoints during the whole scan,
+/// comparing the number of dropped elements to the number of elements read since the previous checkpoint.
+///
+/// With `WORK_GUARD` the scan also aborts once it has done as much work as sorting everything with the fallback would,
+/// so that a full sort is `O(N * log(N))` no matter how the input was crafted.
+///
+/// With `config.max_dropped` the dropped elements are sorted with `dropped_sorter` and merged into the kept elements
+/// whenever there are that many of them, after which the scan continues. With `MaxDroppedPolicy::Fallback` we abort instead.
+///
+/// If growing `s.dropped` fails (only possible with `s.fallible_alloc` and `DropToBuffer`) the scan is aborted too,
+/// and the error is left in `s.alloc_error`.
+///
+/// Once `s.interrupted` has been set the compare function answers `Equal`, so we check it on every such answer
+/// and then abort too, whether or not `config.early_out` is set.
+///
+/// With `R::ENABLED` we keep whole stretches of in-order elements at once. We make the same decisions,
+/// just faster, since `R::in_order_len` must agree with `compare`.
+///
+/// With `H::REMOVE` an element equal to the last kept one is removed instead of kept,
+/// and `s.merge_dropped_dedup` must be used to merge. This doesn't work with `S::IN_PLACE`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) unsafe fn find_lns<T, C, R, S, H, D, F>(
+    s: &mut DmSorter<T, C>,
+    config: &DmSortConfig,
+    stats: &mut SortStats,
+    _run_scan: R,
+    storage: &mut S,
+    duplicates: &mut H,
+    dropped_sorter: &mut D,
+    compare: &mut F,
+) -> bool
+where
+    C: SortColumns,
+    R: RunScan<T>,
+    S: DroppedStorage<T, C>,
+    H: Duplicates<T>,
+    D: SubSorter<T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    debug_assert!(!(S::IN_PLACE && H::REMOVE));
 
-        // ------------------------------------------------------------------------
+    let len = s.slice.len();
+    let recency = cmp::max(config.recency, 1);
+    let max_dropped = cmp::max(config.max_dropped, 1);
+    let mut num_dropped_in_row = 0;
+    let mut num_kept_in_row = 0;
+    let mut num_flushed = 0; // Dropped elements that have been merged into the kept ones.
+    let mut read = s.write;
+    let mut iteration = 0;
 
-        let mut num_dropped_in_row = 0;
-        let mut read = 0;
-        let mut iteration = 0;
-        let ealy_out_stop = s.slice.len() / EARLY_OUT_TEST_AT;
+    // The disorder check is done when `iteration == next_checkpoint`:
+    let mut checkpoint = 0;
+    let mut next_checkpoint = if config.early_out {
+        (len as f32 * config.early_out_first_checkpoint) as usize
+    } else {
+        usize::MAX
+    };
+    let mut read_at_checkpoint = read;
+    let mut dropped_at_checkpoint = 0;
 
-        while read < s.slice.len() {
-            iteration += 1;
-            if EARLY_OUT
-                && iteration == ealy_out_stop
-                && s.dropped.len() as f32 > read as f32 * EARLY_OUT_DISORDER_FRACTION
-            {
+    // Each iteration is one unit of work. Backtracking does extra work which we count here:
+    let mut backtracking_work = 0;
+    let work_budget = work_budget(len);
+
+    while read < len {
+        iteration += 1;
+        if iteration == next_checkpoint {
+            let num_read = read.saturating_sub(read_at_checkpoint);
+            let num_dropped =
+                (num_flushed + storage.num_dropped(s)).saturating_sub(dropped_at_checkpoint);
+            if num_dropped as f32 > num_read as f32 * config.early_out_disorder_fraction {
                 // We have seen a lot of the elements and dropped a lot of them.
                 // This doesn't look good. Abort.
-                ptr::copy_nonoverlapping(
-                    s.dropped.as_ptr(),
-                    &mut s.slice[s.write],
-                    s.dropped.len(),
-                );
-                s.dropped.set_len(0);
-                s.slice.sort_unstable_by(|a, b| compare(a, b));
-                return;
+                stats.num_dropped = num_flushed + storage.num_dropped(s);
+                stats.early_out = Some(EarlyOut::Disorder {
+                    checkpoint,
+                    num_read: read,
+                });
+                storage.restore(s);
+                return false;
             }
 
-            if s.write == 0
-                || compare(
-                    s.slice.get_unchecked(read),
-                    s.slice.get_unchecked(s.write - 1),
-                ) != Ordering::Less
-            {
-                // The element is order - keep it:
-                if read != s.write {
-                    unsafe_copy(s.slice, read, s.write);
+            checkpoint += 1;
+            next_checkpoint = cmp::max(
+                next_checkpoint + 1,
+                (next_checkpoint as f32 * config.early_out_checkpoint_growth) as usize,
+            );
+            read_at_checkpoint = read;
+            dropped_at_checkpoint = num_flushed + storage.num_dropped(s);
+        }
+
+        let ordering = if s.write == 0 {
+            Ordering::Greater
+        } else {
+            compare(
+                s.slice.get_unchecked(read),
+                s.slice.get_unchecked(s.write - 1),
+            )
+        };
+
+        if ordering == Ordering::Equal && s.is_interrupted() {
+            // The compare function failed, and answers `Equal` from now on. Stop right away:
+            stats.num_dropped = num_flushed + storage.num_dropped(s);
+            storage.restore(s);
+            return false;
+        }
+
+        if H::REMOVE && ordering == Ordering::Equal {
+            // A duplicate of the last kept element - remove it:
+            s.remove_duplicate(read, s.write - 1, duplicates);
+            read += 1;
+            num_dropped_in_row = 0;
+        } else if ordering != Ordering::Less {
+            // The element is order - keep it:
+            if read != s.write {
+                storage.keep(s, read, s.write);
+            }
+            read += 1;
+            s.write += 1;
+            num_dropped_in_row = 0;
+
+            num_kept_in_row += 1;
+            if R::ENABLED && !H::REMOVE && num_kept_in_row >= RUN_SCAN_MIN_KEPT {
+                // Keep the in-order elements after it too. We stop before the next checkpoint,
+                // which must be done at the same iteration as when keeping them one by one:
+                let max_run_len =
+                    cmp::min(len - read, next_checkpoint.saturating_sub(iteration + 1));
+                let run_len = R::in_order_len(&s.slice[(read - 1)..(read + max_run_len)]);
+                if run_len > 0 {
+                    if read != s.write {
+                        storage.keep_block(s, read, s.write, run_len);
+                    }
+                    read += run_len;
+                    s.write += run_len;
+                    iteration += run_len;
                 }
-                read += 1;
-                s.write += 1;
+            }
+        } else {
+            num_kept_in_row = 0;
+            if storage.num_dropped(s) >= max_dropped {
+                // No room for another dropped element.
+                if config.max_dropped_policy == MaxDroppedPolicy::Fallback {
+                    stats.num_dropped = num_flushed + storage.num_dropped(s);
+                    stats.early_out = Some(EarlyOut::MaxDropped { num_read: read });
+                    storage.restore(s);
+                    return false;
+                }
+
+                // Make room by merging the dropped elements into the kept ones,
+                // and then look at this element again:
+                let num_dropped = storage.num_dropped(s);
+                let num_moved = storage.flush(s, duplicates, dropped_sorter, compare);
+                num_flushed += num_dropped;
+                stats.num_flushes += 1;
                 num_dropped_in_row = 0;
-            } else {
-                if DOUBLE_COMPARISONS
-                    && num_dropped_in_row == 0
-                    && 2 <= s.write
-                    && compare(
-                        s.slice.get_unchecked(read),
-                        s.slice.get_unchecked(s.write - 2),
-                    ) != Ordering::Less
-                {
-                    // Quick undo: drop previously accepted element, and overwrite with new one:
-                    unsafe_push(&mut s.dropped, s.slice.get_unchecked(s.write - 1));
-                    unsafe_copy(s.slice, read, s.write - 1);
+
+                // Block moves are much cheaper than comparisons:
+                backtracking_work += num_dropped + num_moved / 16;
+                if WORK_GUARD && iteration + backtracking_work > work_budget {
+                    stats.num_dropped = num_flushed;
+                    stats.early_out = Some(EarlyOut::WorkBudget { num_read: read });
+                    storage.restore(s);
+                    return false;
+                }
+                continue;
+            }
+
+            if DOUBLE_COMPARISONS && num_dropped_in_row == 0 && 2 <= s.write {
+                let ordering = compare(
+                    s.slice.get_unchecked(read),
+                    s.slice.get_unchecked(s.write - 2),
+                );
+                if ordering != Ordering::Less {
+                    // Quick undo: drop previously accepted element...
+                    let last_kept = s.write - 1;
+                    if let Err(err) = storage.push(s, last_kept) {
+                        return abort_on_alloc_error(s, storage, stats, err, read);
+                    }
+                    if H::REMOVE && ordering == Ordering::Equal {
+                        // ...and remove the new one, which is a duplicate of the one before:
+                        s.write -= 1;
+                        s.remove_duplicate(read, s.write - 1, duplicates);
+                    } else {
+                        // ...and overwrite with new one:
+                        storage.keep(s, read, last_kept);
+                    }
                     read += 1;
                     continue;
                 }
+            }
 
-                if num_dropped_in_row < RECENCY {
-                    // Drop it:
-                    unsafe_push(&mut s.dropped, s.slice.get_unchecked(read));
-                    read += 1;
-                    num_dropped_in_row += 1;
-                } else {
-                    // Undo dropping the last num_dropped_in_row elements:
-                    let trunc_to_length = s.dropped.len() - num_dropped_in_row;
-                    s.dropped.set_len(trunc_to_length);
-                    read -= num_dropped_in_row;
-
-                    let mut num_backtracked = 1;
-                    s.write -= 1;
-
-                    if FAST_BACKTRACKING {
-                        // Back-track until we can accept at least one of the recently dropped elements:
-                        let max_of_dropped = s.slice[read..(read + num_dropped_in_row + 1)]
-                            .iter()
-                            .max_by(|a, b| compare(a, b))
-                            .unwrap();
-
-                        while 1 <= s.write
-                            && compare(max_of_dropped, s.slice.get_unchecked(s.write - 1))
-                                == Ordering::Less
-                        {
-                            num_backtracked += 1;
-                            s.write -= 1;
-                        }
-                    }
+            if num_dropped_in_row < recency {
+                // Drop it:
+                if let Err(err) = storage.push(s, read) {
+                    return abort_on_alloc_error(s, storage, stats, err, read);
+                }
+                read += 1;
+                num_dropped_in_row += 1;
+            } else {
+                // Undo dropping the last num_dropped_in_row elements:
+                let trunc_to_length = storage.num_dropped(s) - num_dropped_in_row;
+                storage.truncate(s, trunc_to_length);
+                read -= num_dropped_in_row;
 
-                    // Append s.slice[read..(read + num_backtracked)] to s.dropped:
-                    {
-                        let old_len = s.dropped.len();
-                        s.dropped.reserve(num_backtracked);
-                        ptr::copy_nonoverlapping(
-                            s.slice.as_ptr().add(s.write),
-                            s.dropped.as_mut_ptr().add(old_len),
-                            num_backtracked,
-                        );
-                        s.dropped.set_len(old_len + num_backtracked);
-                    }
+                let mut num_backtracked = 1;
+                s.write -= 1;
+
+                if FAST_BACKTRACKING {
+                    // Back-track until we can accept at least one of the recently dropped elements:
+                    let max_of_dropped = s.slice[read..(read + num_dropped_in_row + 1)]
+                        .iter()
+                        .max_by(|a, b| compare(a, b))
+                        .unwrap();
 
-                    num_dropped_in_row = 0;
+                    // The kept elements are sorted, so we can gallop back to the retreat point,
+                    // but not further than there is room for:
+                    let num_greater =
+                        gallop_from_back(&s.slice[..s.write], max_of_dropped, compare);
+                    let num_greater =
+                        cmp::min(num_greater, max_dropped - storage.num_dropped(s) - 1);
+                    num_backtracked += num_greater;
+                    s.write -= num_greater;
+                }
+
+                // Drop s.slice[write..(write + num_backtracked)] too:
+                let write = s.write;
+                if let Err(err) = storage.append(s, write, num_backtracked) {
+                    // Keep the backtracked elements, so that the gap is at slice[write..read] again:
+                    s.write += num_backtracked;
+                    return abort_on_alloc_error(s, storage, stats, err, read);
+                }
+
+                num_dropped_in_row = 0;
+
+                // Going back and forth is the only way the scan can do more than O(N) work:
+                backtracking_work += recency + num_backtracked;
+                if WORK_GUARD && iteration + backtracking_work > work_budget {
+                    stats.num_dropped = num_flushed + storage.num_dropped(s);
+                    stats.early_out = Some(EarlyOut::WorkBudget { num_read: read });
+                    storage.restore(s);
+                    return false;
                 }
             }
         }
+    }
 
-        // ------------------------------------------------------------------------
+    stats.num_dropped = num_flushed + storage.num_dropped(s);
+    true
+}
 
-        s.dropped.sort_unstable_by(|a, b| compare(a, b));
+/// Give up on the scan of `find_lns` because we could not allocate room for more dropped elements.
+#[cold]
+unsafe fn abort_on_alloc_error<T, C: SortColumns, S: DroppedStorage<T, C>>(
+    s: &mut DmSorter<T, C>,
+    storage: &mut S,
+    stats: &mut SortStats,
+    err: TryReserveError,
+    read: usize,
+) -> bool {
+    stats.num_dropped = storage.num_dropped(s);
+    stats.early_out = Some(EarlyOut::AllocFailure { num_read: read });
+    s.alloc_error = Some(err);
+    storage.restore(s);
+    false
+}
 
-        // ------------------------------------------------------------------------
-        // Merge:
+/// Sorts the slice, applying every move to the companion columns as well.
+///
+/// `dropped_sorter` sorts the dropped elements and `fallback_sorter` sorts everything on early-out.
+pub(crate) fn sort_columns_move_by<T, C, D, B, F>(
+    slice: &mut [T],
+    columns: C,
+    config: &DmSortConfig,
+    dropped_sorter: &mut D,
+    fallback_sorter: &mut B,
+    compare: F,
+) -> SortStats
+where
+    C: SortColumns,
+    D: SubSorter<T>,
+    B: SubSorter<T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let result = try_sort_columns_move_by(
+        slice,
+        columns,
+        config,
+        0,
+        None,
+        None,
+        NoRunScan,
+        dropped_sorter,
+        fallback_sorter,
+        compare,
+    );
+    match result {
+        Ok(stats) => stats,
+        Err(_) => unreachable!("Only fallible allocations can fail"),
+    }
+}
 
-        let mut back = s.slice.len();
+/// Like `sort_columns_move_by`, but with `Some(alloc_policy)` the dropped elements are allocated with `try_reserve`.
+/// If that fails we either sort in place with `fallback_sorter` or return the error, depending on `alloc_policy`.
+/// `run_scan` can speed up the scan for primitives.
+/// The first `sorted_prefix_len` elements must already be in order, and are kept without comparing them again.
+/// Once `interrupted` is set we stop as soon as we can, leaving all elements in the slice in an unspecified order.
+/// The sub-sorters must then stop too.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn try_sort_columns_move_by<T, C, R, D, B, F>(
+    slice: &mut [T],
+    columns: C,
+    config: &DmSortConfig,
+    sorted_prefix_len: usize,
+    alloc_policy: Option<AllocPolicy>,
+    interrupted: Option<&Cell<bool>>,
+    run_scan: R,
+    dropped_sorter: &mut D,
+    fallback_sorter: &mut B,
+    mut compare: F,
+) -> Result<SortStats, TryReserveError>
+where
+    C: SortColumns,
+    R: RunScan<T>,
+    D: SubSorter<T>,
+    B: SubSorter<T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut stats = SortStats {
+        len: slice.len(),
+        ..Default::default()
+    };
 
-        loop {
-            let old_len = s.dropped.len();
-            if old_len == 0 {
-                break;
-            }
-            {
-                let last_dropped = s.dropped.get_unchecked(old_len - 1);
-                while 0 < s.write
-                    && compare(last_dropped, s.slice.get_unchecked(s.write - 1)) == Ordering::Less
-                {
-                    unsafe_copy(s.slice, s.write - 1, back - 1);
-                    back -= 1;
-                    s.write -= 1;
+    unsafe {
+        let mut s = DmSorter::with_columns(slice, columns);
+        s.fallible_alloc = alloc_policy.is_some();
+        s.max_dropped = config.max_dropped;
+        s.write = sorted_prefix_len;
+        s.interrupted = interrupted;
+
+        if s.slice.len() < 2 {
+            return Ok(stats);
+        }
+
+        if config.precheck {
+            let precheck = precheck_by(s.slice, config, &mut compare);
+            stats.precheck = Some(precheck);
+            match precheck.algorithm {
+                Algorithm::InsertionSort => {
+                    s.sort_tail_by(0, &mut InsertionSort, &mut compare);
+                    return Ok(stats);
+                }
+                Algorithm::Fallback => {
+                    s.sort_tail_by(0, fallback_sorter, &mut compare);
+                    return Ok(stats);
                 }
-                ptr::copy_nonoverlapping(last_dropped, s.slice.get_unchecked_mut(back - 1), 1);
+                Algorithm::DropMerge => {}
             }
-            back -= 1;
-            s.dropped.set_len(old_len - 1);
         }
+
+        let alloc_error = if find_lns(
+            &mut s,
+            config,
+            &mut stats,
+            run_scan,
+            &mut DropToBuffer,
+            &mut KeepDuplicates,
+            dropped_sorter,
+            &mut compare,
+        ) {
+            s.sort_dropped_by(dropped_sorter, &mut compare);
+            None
+        } else if let Some(err) = s.alloc_error.take() {
+            Some(err)
+        } else if s.is_interrupted() {
+            // Don't spend any more work on the tail. Its order doesn't matter anymore:
+            return Ok(stats);
+        } else if s.write < (s.slice.len() as f32 * SALVAGE_MIN_FRACTION) as usize
+            || s.slice.len() - s.write > config.max_dropped
+        {
+            s.sort_tail_by(0, fallback_sorter, &mut compare);
+            return Ok(stats);
+        } else {
+            // We gave up, but slice[..write] is still sorted. Only sort the rest,
+            // and then merge it in as if it was the dropped elements:
+            let write = s.write;
+            s.sort_tail_by(write, fallback_sorter, &mut compare);
+            let result = s.append_dropped(write, s.slice.len() - write);
+            if result.is_ok() {
+                stats.num_salvaged = write;
+            }
+            result.err()
+        };
+
+        if s.is_interrupted() {
+            // Any dropped elements are put back into the gap when `s` is dropped.
+            return Ok(stats);
+        }
+
+        if let Some(err) = alloc_error {
+            // There was no room for the dropped elements, but they are all back in the slice:
+            if alloc_policy == Some(AllocPolicy::Error) {
+                return Err(err);
+            }
+            s.sort_tail_by(0, fallback_sorter, &mut compare);
+            return Ok(stats);
+        }
+
+        // ------------------------------------------------------------------------
+        // Merge:
+
+        s.merge_dropped(&mut compare);
     }
+
+    Ok(stats)
+}
+
+pub(crate) fn sort_move_by<T, F>(slice: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    sort_columns_move_by(
+        slice,
+        (),
+        &DmSortConfig::default(),
+        &mut SortUnstable,
+        &mut SortUnstable,
+        compare,
+    );
 }
 
 // ----------------------------------------------------------------------------
@@ -399,6 +1310,66 @@ where
     sort_move_by(slice, compare);
 }
 
+/// Sorts the elements using the given compare function,
+/// with `dropped_sorter` for sorting the out-of-order elements
+/// and `fallback_sorter` for sorting everything when the input is too disordered.
+///
+/// `sort_by` is the same as using [`SortUnstable`](struct.SortUnstable.html) for both.
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::sort_by_with_sorters(
+///     &mut numbers,
+///     &mut dmsort::InsertionSort,
+///     &mut dmsort::SortStable,
+///     |a, b| b.cmp(a),
+/// );
+/// assert_eq!(numbers, vec!(7, 6, 5, 4, 3, 2, 1, 0));
+/// ```
+pub fn sort_by_with_sorters<T, D, B, F>(
+    slice: &mut [T],
+    dropped_sorter: &mut D,
+    fallback_sorter: &mut B,
+    compare: F,
+) where
+    D: SubSorter<T>,
+    B: SubSorter<T>,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    sort_columns_move_by(
+        slice,
+        (),
+        &DmSortConfig::default(),
+        dropped_sorter,
+        fallback_sorter,
+        compare,
+    );
+}
+
+/// Sorts the elements using the given compare function and configuration,
+/// and returns statistics about what happened.
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 2, 9, 3, 4, 5, 6);
+/// let stats = dmsort::sort_by_with_config(&mut numbers, &Default::default(), |a, b| a.cmp(b));
+/// assert_eq!(numbers, vec!(0, 1, 2, 3, 4, 5, 6, 9));
+/// assert_eq!(stats.num_dropped, 1);
+/// assert_eq!(stats.early_out, None);
+/// ```
+pub fn sort_by_with_config<T, F>(slice: &mut [T], config: &DmSortConfig, compare: F) -> SortStats
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    sort_columns_move_by(
+        slice,
+        (),
+        config,
+        &mut SortUnstable,
+        &mut SortUnstable,
+        compare,
+    )
+}
+
 /// Sorts the elements using the given key function.
 /// # Examples
 /// ```
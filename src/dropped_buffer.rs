@@ -0,0 +1,162 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp;
+use std::collections::TryReserveError;
+use std::mem::{self, MaybeUninit};
+use std::ops::{Deref, DerefMut};
+use std::ptr;
+use std::slice;
+
+// ----------------------------------------------------------------------------
+
+/// How many bytes of dropped elements we keep on the stack before moving them to the heap.
+/// This is 128 `i32` or 21 `String`, which covers the common case of a handful of outliers.
+const INLINE_BYTES: usize = 512;
+
+/// Raw bytes, aligned for most element types.
+#[repr(C, align(16))]
+struct InlineBytes([MaybeUninit<u8>; INLINE_BYTES]);
+
+/// Temporary storage of the dropped elements, which starts out in an inline array
+/// and spills over to a `Vec` when that is full.
+///
+/// Like `DmSorter` it only bit-copies the elements and never drops them:
+/// they must all be moved out again before the buffer is dropped.
+pub(crate) struct DroppedBuffer<T> {
+    inline: InlineBytes,
+
+    /// Used once we have spilled. Its length is always zero: we only use its memory.
+    heap: Vec<T>,
+
+    len: usize,
+    spilled: bool,
+}
+
+impl<T> DroppedBuffer<T> {
+    pub(crate) fn new() -> Self {
+        DroppedBuffer {
+            inline: InlineBytes([MaybeUninit::uninit(); INLINE_BYTES]),
+            heap: Vec::new(),
+            len: 0,
+            spilled: Self::inline_capacity() == 0,
+        }
+    }
+
+    /// Zero for zero-sized and over-aligned types, which always go in the `Vec`.
+    #[inline(always)]
+    fn inline_capacity() -> usize {
+        if mem::size_of::<T>() == 0 || mem::align_of::<T>() > mem::align_of::<InlineBytes>() {
+            0
+        } else {
+            INLINE_BYTES / mem::size_of::<T>()
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn capacity(&self) -> usize {
+        if self.spilled {
+            self.heap.capacity()
+        } else {
+            Self::inline_capacity()
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn as_ptr(&self) -> *const T {
+        if self.spilled {
+            self.heap.as_ptr()
+        } else {
+            self.inline.0.as_ptr() as *const T
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn as_mut_ptr(&mut self) -> *mut T {
+        if self.spilled {
+            self.heap.as_mut_ptr()
+        } else {
+            self.inline.0.as_mut_ptr() as *mut T
+        }
+    }
+
+    /// The first `len` elements must be initialized.
+    #[inline(always)]
+    pub(crate) unsafe fn set_len(&mut self, len: usize) {
+        debug_assert!(len <= self.capacity());
+        self.len = len;
+    }
+
+    /// Make sure there is room for `needed` elements, using `reserve_exact` to give a new `Vec` that capacity.
+    ///
+    /// `Vec::reserve` only keeps the first `len` elements when it reallocates, and the length of `self.heap` is zero.
+    /// So we always allocate a new `Vec` and copy the elements over, both when spilling over and when growing.
+    #[cold]
+    fn grow_with<R>(&mut self, needed: usize, reserve_exact: R) -> Result<(), TryReserveError>
+    where
+        R: FnOnce(&mut Vec<T>, usize) -> Result<(), TryReserveError>,
+    {
+        if needed <= self.capacity() {
+            return Ok(());
+        }
+
+        let mut heap = Vec::new();
+        reserve_exact(&mut heap, needed)?;
+        unsafe {
+            ptr::copy_nonoverlapping(self.as_ptr(), heap.as_mut_ptr(), self.len);
+        }
+        self.heap = heap;
+        self.spilled = true;
+        Ok(())
+    }
+
+    /// Room for `additional` more elements, but at least double the capacity like `Vec::reserve`.
+    fn amortized_capacity(&self, additional: usize) -> usize {
+        cmp::max(
+            self.len.saturating_add(additional),
+            self.capacity().saturating_mul(2),
+        )
+    }
+
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        let needed = self.amortized_capacity(additional);
+        let _ = self.grow_with(needed, |vec, capacity| {
+            vec.reserve_exact(capacity);
+            Ok(())
+        });
+    }
+
+    pub(crate) fn reserve_exact(&mut self, additional: usize) {
+        let needed = self.len.saturating_add(additional);
+        let _ = self.grow_with(needed, |vec, capacity| {
+            vec.reserve_exact(capacity);
+            Ok(())
+        });
+    }
+
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let needed = self.amortized_capacity(additional);
+        self.grow_with(needed, |vec, capacity| vec.try_reserve_exact(capacity))
+    }
+
+    pub(crate) fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let needed = self.len.saturating_add(additional);
+        self.grow_with(needed, |vec, capacity| vec.try_reserve_exact(capacity))
+    }
+}
+
+impl<T> Deref for DroppedBuffer<T> {
+    type Target = [T];
+
+    #[inline(always)]
+    fn deref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.len) }
+    }
+}
+
+impl<T> DerefMut for DroppedBuffer<T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut [T] {
+        let len = self.len;
+        unsafe { slice::from_raw_parts_mut(self.as_mut_ptr(), len) }
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+use dmsort::sort_by;
+
+// ----------------------------------------------------------------------------
+
+/// A floating point type that can be sorted with [`sort_floats`](fn.sort_floats.html).
+pub trait Float: Copy {
+    /// The IEEE 754 `totalOrder` predicate, e.g. [`f32::total_cmp`](https://doc.rust-lang.org/std/primitive.f32.html#method.total_cmp).
+    fn total_cmp(&self, other: &Self) -> Ordering;
+}
+
+impl Float for f32 {
+    #[inline(always)]
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f32::total_cmp(self, other)
+    }
+}
+
+impl Float for f64 {
+    #[inline(always)]
+    fn total_cmp(&self, other: &Self) -> Ordering {
+        f64::total_cmp(self, other)
+    }
+}
+
+/// Sorts `f32` or `f64` using the IEEE 754 total order.
+///
+/// This never panics, even with NaN in the input. The order is:
+/// negative NaN, negative infinity, negative numbers, `-0.0`, `+0.0`, positive numbers, positive infinity, positive NaN.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<f32> = vec!(0.0, 1.0, 6.0, 7.0, 2.0, -0.0, f32::NAN, 5.0);
+/// dmsort::sort_floats(&mut numbers);
+/// assert_eq!(&numbers[..7], &[-0.0, 0.0, 1.0, 2.0, 5.0, 6.0, 7.0]);
+/// assert!(numbers[7].is_nan());
+/// ```
+pub fn sort_floats<T: Float>(slice: &mut [T]) {
+    sort_by(slice, |a, b| a.total_cmp(b));
+}
+
+// ----------------------------------------------------------------------------
+
+/// What [`sort_by_partial_key`](fn.sort_by_partial_key.html) should do with NaN keys,
+/// i.e. keys that cannot be compared to themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NanPolicy {
+    /// Put all elements with NaN keys at the start of the slice.
+    First,
+
+    /// Put all elements with NaN keys at the end of the slice.
+    Last,
+
+    /// Leave the slice untouched and return `Err(NanError)` if any key is NaN.
+    Error,
+}
+
+/// Returned by [`sort_by_partial_key`](fn.sort_by_partial_key.html) when a key is NaN and the policy is `NanPolicy::Error`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NanError;
+
+impl fmt::Display for NanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("cannot sort by a NaN key")
+    }
+}
+
+impl Error for NanError {}
+
+#[inline(always)]
+fn is_nan<K: PartialOrd>(key: &K) -> bool {
+    key.partial_cmp(key).is_none()
+}
+
+/// Sorts the elements using a key function whose keys are only `PartialOrd`, e.g. `f32`.
+///
+/// Keys that can't be compared to themselves (NaN) are handled according to `nan_policy`.
+/// Other incomparable keys are treated as equal.
+///
+/// # Examples
+/// ```
+/// use dmsort::NanPolicy;
+///
+/// let mut depths : Vec<f32> = vec!(0.0, 1.0, f32::NAN, 7.0, 2.0, 3.0);
+/// dmsort::sort_by_partial_key(&mut depths, |&x| x, NanPolicy::Last).unwrap();
+/// assert_eq!(&depths[..5], &[0.0, 1.0, 2.0, 3.0, 7.0]);
+/// assert!(depths[5].is_nan());
+///
+/// assert!(dmsort::sort_by_partial_key(&mut depths, |&x| x, NanPolicy::Error).is_err());
+/// ```
+pub fn sort_by_partial_key<T, K, F>(
+    slice: &mut [T],
+    mut key: F,
+    nan_policy: NanPolicy,
+) -> Result<(), NanError>
+where
+    K: PartialOrd,
+    F: FnMut(&T) -> K,
+{
+    let nan_ordering = match nan_policy {
+        NanPolicy::First => Ordering::Less,
+        NanPolicy::Last => Ordering::Greater,
+        NanPolicy::Error => {
+            if slice.iter().any(|x| is_nan(&key(x))) {
+                return Err(NanError);
+            }
+            Ordering::Equal
+        }
+    };
+
+    sort_by(slice, |a, b| {
+        let (a, b) = (key(a), key(b));
+        match a.partial_cmp(&b) {
+            Some(ordering) => ordering,
+            None => match (is_nan(&a), is_nan(&b)) {
+                (true, false) => nan_ordering,
+                (false, true) => nan_ordering.reverse(),
+                _ => Ordering::Equal,
+            },
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+use std::collections::TryReserveError;
+
+use config::DmSortConfig;
+use dedup::{Duplicates, KeepDuplicates};
+use dmsort::{find_lns, DmSorter, DroppedStorage, SALVAGE_MIN_FRACTION};
+use run_scan::NoRunScan;
+use stats::SortStats;
+use sub_sorter::{SortUnstable, SubSorter};
+
+// ----------------------------------------------------------------------------
+
+/// Leave the dropped elements in the gap `slice[write..read]`, so no extra memory is needed.
+/// The elements are only ever swapped, so the slice always holds all of them, even if `compare` panics.
+///
+/// When we keep an element we swap it with the first element of the gap.
+/// The most recently dropped elements are therefore always right before `read`,
+/// which is what backtracking needs to undo dropping them.
+struct DropInPlace {
+    num_dropped: usize,
+}
+
+impl<T> DroppedStorage<T, ()> for DropInPlace {
+    const IN_PLACE: bool = true;
+
+    #[inline(always)]
+    fn num_dropped(&self, _s: &DmSorter<T>) -> usize {
+        self.num_dropped
+    }
+
+    #[inline(always)]
+    unsafe fn keep(&mut self, s: &mut DmSorter<T>, source: usize, dest: usize) {
+        s.slice.swap(source, dest);
+    }
+
+    #[inline(always)]
+    unsafe fn keep_block(&mut self, s: &mut DmSorter<T>, source: usize, dest: usize, count: usize) {
+        s.slice[dest..(source + count)].rotate_left(source - dest);
+    }
+
+    #[inline(always)]
+    unsafe fn push(&mut self, _s: &mut DmSorter<T>, _index: usize) -> Result<(), TryReserveError> {
+        self.num_dropped += 1;
+        Ok(())
+    }
+
+    #[inline(always)]
+    unsafe fn append(
+        &mut self,
+        _s: &mut DmSorter<T>,
+        _index: usize,
+        count: usize,
+    ) -> Result<(), TryReserveError> {
+        self.num_dropped += count;
+        Ok(())
+    }
+
+    #[inline(always)]
+    unsafe fn truncate(&mut self, _s: &mut DmSorter<T>, len: usize) {
+        self.num_dropped = len;
+    }
+
+    unsafe fn restore(&mut self, _s: &mut DmSorter<T>) {
+        self.num_dropped = 0;
+    }
+
+    unsafe fn flush<H, D, F>(
+        &mut self,
+        s: &mut DmSorter<T>,
+        _duplicates: &mut H,
+        dropped_sorter: &mut D,
+        compare: &mut F,
+    ) -> usize
+    where
+        H: Duplicates<T>,
+        D: SubSorter<T>,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let num_kept = s.write;
+        let end = num_kept + self.num_dropped;
+        dropped_sorter.sort_by(&mut s.slice[num_kept..end], |a, b| compare(a, b));
+        merge_in_place_by(&mut s.slice[..end], num_kept, compare);
+        s.write = end;
+        self.num_dropped = 0;
+        num_kept // The merge may move all of them.
+    }
+}
+
+/// Merges the sorted `slice[..mid]` and `slice[mid..]` in place, by recursively splitting the longer one
+/// in half, finding where the middle element goes in the other one, and swapping the blocks in between.
+///
+/// This needs `O(log(N))` stack but no other memory. It does `O(N * log(N))` moves in the worst case,
+/// but when one side is short it is close to a binary insertion of each of its elements.
+fn merge_in_place_by<T, F>(slice: &mut [T], mid: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = slice.len();
+    if mid == 0 || mid == len || compare(&slice[mid], &slice[mid - 1]) != Ordering::Less {
+        return; // Already in order.
+    }
+    if len == 2 {
+        slice.swap(0, 1);
+        return;
+    }
+
+    let (first_cut, second_cut) = if len - mid <= mid {
+        // Everything in the right half that is less than the middle of the left half goes before it:
+        let first_cut = mid / 2;
+        let (left, right) = slice.split_at(mid);
+        let num_less = right.partition_point(|x| compare(x, &left[first_cut]) == Ordering::Less);
+        (first_cut, mid + num_less)
+    } else {
+        // Everything in the left half that is not greater than the middle of the right half stays before it:
+        let second_cut = mid + (len - mid) / 2;
+        let (left, right) = slice.split_at(mid);
+        let pivot = &right[second_cut - mid];
+        let num_not_greater = left.partition_point(|x| compare(pivot, x) != Ordering::Less);
+        (num_not_greater, second_cut)
+    };
+
+    slice[first_cut..second_cut].rotate_left(mid - first_cut);
+    let new_mid = first_cut + (second_cut - mid);
+    merge_in_place_by(&mut slice[..new_mid], first_cut, compare);
+    merge_in_place_by(&mut slice[new_mid..], second_cut - new_mid, compare);
+}
+
+fn sort_in_place_with_config<T, F>(
+    slice: &mut [T],
+    config: &DmSortConfig,
+    mut compare: F,
+) -> SortStats
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let mut stats = SortStats {
+        len: slice.len(),
+        ..Default::default()
+    };
+
+    if slice.len() < 2 {
+        return stats;
+    }
+
+    let mut s = DmSorter::new(slice);
+    let finished = unsafe {
+        find_lns(
+            &mut s,
+            config,
+            &mut stats,
+            NoRunScan,
+            &mut DropInPlace { num_dropped: 0 },
+            &mut KeepDuplicates,
+            &mut SortUnstable,
+            &mut compare,
+        )
+    };
+    let write = s.write;
+
+    if !finished {
+        if write < (s.slice.len() as f32 * SALVAGE_MIN_FRACTION) as usize {
+            s.slice.sort_unstable_by(|a, b| compare(a, b));
+            return stats;
+        }
+        stats.num_salvaged = write;
+    }
+
+    // The dropped elements, and on early-out also the unread ones, are all in slice[write..]:
+    s.slice[write..].sort_unstable_by(|a, b| compare(a, b));
+    merge_in_place_by(s.slice, write, &mut compare);
+
+    stats
+}
+
+// ----------------------------------------------------------------------------
+
+/// Sorts the elements using the given compare function, without allocating any memory.
+///
+/// The out-of-order elements are collected at the end of the slice instead of in a separate buffer,
+/// sorted there with `sort_unstable_by`, and then merged in place by swapping blocks of elements around.
+/// This does more moves than [`sort_by`](fn.sort_by.html), which makes it up to twice as slow, see the
+/// [benchmarks in the README](https://github.com/emilk/drop-merge-sort#limitations-and-future-work).
+/// Above roughly 25% disorder both give up and use `sort_unstable_by`.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::sort_in_place_by(&mut numbers, |a, b| b.cmp(a));
+/// assert_eq!(numbers, vec!(7, 6, 5, 4, 3, 2, 1, 0));
+/// ```
+pub fn sort_in_place_by<T, F>(slice: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    sort_in_place_with_config(slice, &DmSortConfig::default(), compare);
+}
+
+/// Sorts the elements using the given key function, without allocating any memory.
+///
+/// See [`sort_in_place_by`](fn.sort_in_place_by.html).
+pub fn sort_in_place_by_key<T, K, F>(slice: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    sort_in_place_by(slice, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Sorts the elements using the Ord trait, without allocating any memory.
+///
+/// See [`sort_in_place_by`](fn.sort_in_place_by.html).
+pub fn sort_in_place<T: Ord>(slice: &mut [T]) {
+    sort_in_place_by(slice, |a, b| a.cmp(b));
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+use std::mem::ManuallyDrop;
+use std::ptr;
+
+use argsort::PermutationIndex;
+use dmsort::sort_move_by;
+
+// ----------------------------------------------------------------------------
+
+/// Sorts `slice` by sorting `perm`, which must be `0..slice.len()`, and then moving the elements.
+fn sort_indirect_by<T, I, F>(slice: &mut [T], mut perm: Vec<I>, mut compare: F)
+where
+    I: PermutationIndex,
+    F: FnMut(&T, &T) -> Ordering,
+{
+    // The elements stay put while `compare` runs, so a panic leaves them all in the slice.
+    sort_move_by(&mut perm, |a, b| unsafe {
+        compare(
+            slice.get_unchecked(a.to_usize()),
+            slice.get_unchecked(b.to_usize()),
+        )
+    });
+    move_into_place(&mut perm, slice);
+}
+
+/// Reorders `slice` so that `slice[i]` becomes what was `slice[perm[i]]`, moving each element once.
+///
+/// Each cycle of the permutation is walked with a hole: we take out its first element,
+/// then pull each element into the hole left by the previous one.
+/// `perm` is reset to the identity as we go, which is how we know what has already been moved.
+/// No user code runs here, so the hole can never be observed.
+pub(crate) fn move_into_place<T, I: PermutationIndex>(perm: &mut [I], slice: &mut [T]) {
+    debug_assert_eq!(perm.len(), slice.len());
+    let elements = slice.as_mut_ptr();
+
+    for start in 0..perm.len() {
+        if perm[start].to_usize() == start {
+            continue;
+        }
+        unsafe {
+            let first = ManuallyDrop::new(ptr::read(elements.add(start)));
+            let mut hole = start;
+            loop {
+                let source = perm[hole].to_usize();
+                perm[hole] = I::from_usize(hole);
+                if source == start {
+                    break;
+                }
+                ptr::copy_nonoverlapping(elements.add(source), elements.add(hole), 1);
+                hole = source;
+            }
+            ptr::copy_nonoverlapping(&*first, elements.add(hole), 1);
+        }
+    }
+}
+
+/// Sorts large elements using the given compare function, by sorting their indices.
+///
+/// Drop-Merge sort moves every element at least twice, and keeps the out-of-order elements in a buffer.
+/// For elements of hundreds of bytes it is faster to run Drop-Merge sort over an array of indices,
+/// and then move each element into its sorted place exactly once.
+/// The extra memory is 4 bytes per element (8 bytes for slices longer than `u32::MAX`).
+///
+/// The result is the same as from sorting the elements directly, and if `compare` panics
+/// all elements are left in the slice, in their original order.
+///
+/// This pays off when more than a few percent of the elements are out of order.
+/// For input that is very nearly sorted, moving the elements along the cycles of the permutation
+/// is slower than the block moves of the direct sort, so [`sort_by`](fn.sort_by.html) never does this by itself:
+///
+/// | Disorder | 512 byte direct | 512 byte indirect |
+/// |---------:|----------------:|------------------:|
+/// |       0% |          2.6 ms |            2.8 ms |
+/// |       1% |         12.8 ms |           15.4 ms |
+/// |      10% |         22.0 ms |           20.6 ms |
+/// |      30% |         52.1 ms |           31.3 ms |
+/// |      50% |         33.9 ms |           28.8 ms |
+///
+/// (Sorting 100k elements of 512 bytes with a fraction of the keys replaced by random values.)
+///
+/// # Examples
+/// ```
+/// let mut records : Vec<[u64; 32]> = (0..8).map(|i| [(i * 5) % 8; 32]).collect();
+/// dmsort::sort_large_by(&mut records, |a, b| a[0].cmp(&b[0]));
+/// assert!(records.iter().map(|r| r[0]).eq(0..8));
+/// ```
+pub fn sort_large_by<T, F>(slice: &mut [T], compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = slice.len();
+    if len <= u32::MAX as usize {
+        let perm: Vec<u32> = (0..len as u32).collect();
+        sort_indirect_by(slice, perm, compare);
+    } else {
+        let perm: Vec<usize> = (0..len).collect();
+        sort_indirect_by(slice, perm, compare);
+    }
+}
+
+/// Sorts large elements using the given key function, by sorting their indices.
+///
+/// See [`sort_large_by`](fn.sort_large_by.html).
+///
+/// # Examples
+/// ```
+/// let mut records : Vec<[i64; 32]> = (0..8).map(|i| [i; 32]).collect();
+/// dmsort::sort_large_by_key(&mut records, |r| -r[0]);
+/// assert!(records.iter().map(|r| r[0]).eq((0..8).rev()));
+/// ```
+pub fn sort_large_by_key<T, K, F>(slice: &mut [T], mut key: F)
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    sort_large_by(slice, |a, b| key(a).cmp(&key(b)));
+}
+
+/// Sorts large elements using the Ord trait, by sorting their indices.
+///
+/// See [`sort_large_by`](fn.sort_large_by.html).
+///
+/// # Examples
+/// ```
+/// let mut records : Vec<[u8; 200]> = (0..8).map(|i| [(i * 5) % 8; 200]).collect();
+/// dmsort::sort_large(&mut records);
+/// assert!(records.iter().map(|r| r[0]).eq(0..8));
+/// ```
+pub fn sort_large<T: Ord>(slice: &mut [T]) {
+    sort_large_by(slice, |a, b| a.cmp(b));
+}
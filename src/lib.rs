@@ -10,6 +10,7 @@
 //! * The unsorted elements are evenly distributed.
 //!
 //! Expected number of comparisons is `O(N + K * log(K))` where `K` is the number of elements not in order.
+//! The worst case is `O(N * log(N))` comparisons, even for adversarial input.
 //! Expected memory usage is `O(K)`.
 //! Works best when `K < 0.2 * N`.
 //! The out-of-order elements are expected to be randomly distributed (NOT clumped).
@@ -35,9 +36,50 @@
 //! }
 //! ```
 
-pub use dmsort::{sort, sort_by, sort_by_key};
+pub use argsort::{
+    apply_permutation_in_place, argsort, argsort_by, argsort_by_key, argsort_by_key_u32,
+    argsort_by_u32, argsort_u32, Permutable, PermutationIndex,
+};
+pub use autotune::{autotune, autotune_by, autotune_by_comparisons};
+pub use config::{DmSortConfig, MaxDroppedPolicy, ParseConfigError};
+pub use dedup::{sort_dedup, sort_dedup_by, sort_dedup_by_key};
+pub use dmsort::{sort, sort_by, sort_by_key, sort_by_with_config, sort_by_with_sorters};
+pub use float::{sort_by_partial_key, sort_floats, Float, NanError, NanPolicy};
+pub use in_place::{sort_in_place, sort_in_place_by, sort_in_place_by_key};
+pub use indirect::{sort_large, sort_large_by, sort_large_by_key};
+pub use precheck::{precheck_by, Algorithm, Precheck};
+pub use radix::{sort_by_radix_key, sort_radix, RadixKey, RadixSort};
+pub use run_scan::{sort_primitives, ScanKey};
+pub use select::{select_nth, select_nth_by, select_nth_by_key};
+pub use stats::{EarlyOut, SortStats};
+pub use strings::{sort_by_str_key, sort_strings};
+pub use sub_sorter::{InsertionSort, SortStable, SortUnstable, SubSorter};
+pub use try_sort::{
+    try_reserve_sort, try_reserve_sort_by, try_reserve_sort_by_key, try_sort_by, try_sort_by_key,
+    AllocPolicy,
+};
+pub use zip::{sort_zip, sort_zip_by, sort_zip_by_key, Column, SortColumns};
 
 /// For in module-level testing only. TODO: this shouldn't be public.
 pub use dmsort::sort_copy;
 
+pub mod adaptive;
+
+mod argsort;
+mod autotune;
+mod config;
+mod dedup;
 mod dmsort;
+mod dropped_buffer;
+mod float;
+mod in_place;
+mod indirect;
+mod precheck;
+mod radix;
+mod run_scan;
+mod select;
+mod stats;
+mod strings;
+mod sub_sorter;
+mod try_sort;
+mod zip;
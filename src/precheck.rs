@@ -0,0 +1,126 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+
+use config::DmSortConfig;
+
+// ----------------------------------------------------------------------------
+
+/// Never use fewer probes than this (unless the slice is shorter).
+const MIN_PROBES: usize = 128;
+
+/// Use this many probes times the square root of the length, within the limits.
+const PROBES_PER_SQRT_LEN: usize = 4;
+
+/// Never use more probes than this.
+const MAX_PROBES: usize = 512;
+
+/// Which algorithm the pre-check picked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Binary insertion sort, for short slices.
+    InsertionSort,
+
+    /// Drop-Merge sort, for nearly sorted slices.
+    DropMerge,
+
+    /// The fallback sort, for slices too disordered for Drop-Merge sort to pay off.
+    Fallback,
+}
+
+/// The result of sampling a slice before sorting it, see [`precheck_by`](fn.precheck_by.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Precheck {
+    /// The number of neighbouring pairs that were compared.
+    pub num_probes: usize,
+
+    /// The number of those pairs that were out of order.
+    pub num_descents: usize,
+
+    /// The algorithm picked based on the above.
+    pub algorithm: Algorithm,
+}
+
+impl Precheck {
+    /// Estimated fraction of elements that are out of order.
+    ///
+    /// This assumes the out-of-order elements are randomly distributed, in which case
+    /// a fraction `d` of them gives `d - d² / 2` descents per neighbouring pair.
+    pub fn estimated_disorder(&self) -> f32 {
+        if self.num_probes == 0 {
+            return 0.0;
+        }
+        let descents_per_probe = self.num_descents as f32 / self.num_probes as f32;
+        1.0 - (1.0 - 2.0 * descents_per_probe).max(0.0).sqrt()
+    }
+}
+
+/// Estimates how disordered the slice is by comparing a few hundred neighbouring pairs,
+/// and picks the sorting algorithm to use from that.
+///
+/// This does `O(sqrt(N))` comparisons and is what [`sort_by_with_config`](fn.sort_by_with_config.html)
+/// does when `DmSortConfig::precheck` is set. It is exposed for debugging.
+///
+/// # Examples
+/// ```
+/// let numbers : Vec<i32> = (0..1000).collect();
+/// let precheck = dmsort::precheck_by(&numbers, &Default::default(), |a, b| a.cmp(b));
+/// assert_eq!(precheck.num_descents, 0);
+/// assert_eq!(precheck.algorithm, dmsort::Algorithm::DropMerge);
+///
+/// let reversed : Vec<i32> = (0..1000).rev().collect();
+/// let precheck = dmsort::precheck_by(&reversed, &Default::default(), |a, b| a.cmp(b));
+/// assert_eq!(precheck.num_descents, precheck.num_probes);
+/// assert_eq!(precheck.algorithm, dmsort::Algorithm::Fallback);
+/// ```
+pub fn precheck_by<T, F>(slice: &[T], config: &DmSortConfig, mut compare: F) -> Precheck
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let num_pairs = slice.len().saturating_sub(1);
+    let num_probes = num_pairs.min(
+        MIN_PROBES
+            .max(PROBES_PER_SQRT_LEN * sqrt(slice.len()))
+            .min(MAX_PROBES),
+    );
+
+    // Probe one pair in each of `num_probes` equally long strides,
+    // at a pseudo-random offset so that we don't fall into step with periodic input:
+    let stride = num_pairs.checked_div(num_probes).unwrap_or(0);
+    // A small seed would give small first outputs, bunching the first probes at the start of their strides,
+    // so spread its bits with an odd (and thus invertible, never zero) multiplier:
+    let mut rng_state = (slice.len() as u64 | 1).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    let mut num_descents = 0;
+    for probe in 0..num_probes {
+        let offset = ((xorshift(&mut rng_state) >> 32) * stride as u64) >> 32;
+        let i = probe * stride + offset as usize;
+        if compare(&slice[i + 1], &slice[i]) == Ordering::Less {
+            num_descents += 1;
+        }
+    }
+
+    let mut precheck = Precheck {
+        num_probes,
+        num_descents,
+        algorithm: Algorithm::DropMerge,
+    };
+
+    if slice.len() <= config.insertion_sort_max_len {
+        precheck.algorithm = Algorithm::InsertionSort;
+    } else if precheck.estimated_disorder() > config.precheck_max_disorder {
+        precheck.algorithm = Algorithm::Fallback;
+    }
+
+    precheck
+}
+
+fn sqrt(x: usize) -> usize {
+    (x as f64).sqrt() as usize
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
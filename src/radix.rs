@@ -0,0 +1,669 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::mem;
+use std::ptr;
+
+use dmsort::sort_by_with_sorters;
+use sub_sorter::SubSorter;
+
+// ----------------------------------------------------------------------------
+
+/// Below this many elements we use a comparison sort instead of radix sort.
+const RADIX_MIN_LEN: usize = 64;
+
+/// Every digit is in `0..NUM_BUCKETS`.
+/// Fixed-width keys use bytes (0-255), variable-width keys use 1-256 for bytes and 0 for end-of-key.
+const NUM_BUCKETS: usize = 257;
+
+/// A key that can be radix sorted, i.e. split into a sequence of digits (most significant first).
+///
+/// Comparing two keys digit by digit must give the same result as `radix_cmp`.
+/// No key may have a digit sequence that is a proper prefix of that of another key of the same type.
+/// Variable-length keys ensure this by ending with a zero digit and using 1-256 for the other digits.
+///
+/// Implemented for the primitive integers, `bool`, `char`, `f32` and `f64` (in IEEE 754 total order),
+/// byte strings (`[u8]`, `Vec<u8>`, `str`, `String`), references to keys, and tuples of up to four keys.
+pub trait RadixKey {
+    /// If all keys of this type have the same number of digits, this is it.
+    /// Such keys are sorted with a least-significant-digit radix sort, others with a most-significant-digit one.
+    /// The digits of fixed-width keys must be bytes (less than 256).
+    const FIXED_DIGITS: Option<usize>;
+
+    /// The number of digits in this key.
+    fn num_digits(&self) -> usize;
+
+    /// The digit at `index`, where 0 is the most significant one. Must be less than 257.
+    fn digit(&self, index: usize) -> u16;
+
+    /// The order of the keys.
+    fn radix_cmp(&self, other: &Self) -> Ordering;
+}
+
+macro_rules! impl_radix_key_for_int {
+    ($($t:ty => $unsigned:ty, $flip:expr;)+) => {
+        $(
+            impl RadixKey for $t {
+                const FIXED_DIGITS: Option<usize> = Some(mem::size_of::<$t>());
+
+                #[inline(always)]
+                fn num_digits(&self) -> usize {
+                    mem::size_of::<$t>()
+                }
+
+                #[inline(always)]
+                fn digit(&self, index: usize) -> u16 {
+                    let shift = 8 * (mem::size_of::<$t>() - 1 - index);
+                    (((*self as $unsigned) ^ $flip) >> shift) as u8 as u16
+                }
+
+                #[inline(always)]
+                fn radix_cmp(&self, other: &Self) -> Ordering {
+                    self.cmp(other)
+                }
+            }
+        )+
+    };
+}
+
+// Signed integers have their sign bit flipped so that negative numbers come first.
+impl_radix_key_for_int! {
+    u8 => u8, 0;
+    u16 => u16, 0;
+    u32 => u32, 0;
+    u64 => u64, 0;
+    u128 => u128, 0;
+    usize => usize, 0;
+    i8 => u8, 1 << 7;
+    i16 => u16, 1 << 15;
+    i32 => u32, 1 << 31;
+    i64 => u64, 1 << 63;
+    i128 => u128, 1 << 127;
+    isize => usize, 1 << (mem::size_of::<usize>() * 8 - 1);
+}
+
+impl RadixKey for bool {
+    const FIXED_DIGITS: Option<usize> = Some(1);
+
+    #[inline(always)]
+    fn num_digits(&self) -> usize {
+        1
+    }
+
+    #[inline(always)]
+    fn digit(&self, _index: usize) -> u16 {
+        *self as u16
+    }
+
+    #[inline(always)]
+    fn radix_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+impl RadixKey for char {
+    const FIXED_DIGITS: Option<usize> = Some(4);
+
+    #[inline(always)]
+    fn num_digits(&self) -> usize {
+        4
+    }
+
+    #[inline(always)]
+    fn digit(&self, index: usize) -> u16 {
+        (*self as u32).digit(index)
+    }
+
+    #[inline(always)]
+    fn radix_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+macro_rules! impl_radix_key_for_float {
+    ($($t:ty => $bits:ty;)+) => {
+        $(
+            impl RadixKey for $t {
+                const FIXED_DIGITS: Option<usize> = Some(mem::size_of::<$t>());
+
+                #[inline(always)]
+                fn num_digits(&self) -> usize {
+                    mem::size_of::<$t>()
+                }
+
+                #[inline(always)]
+                fn digit(&self, index: usize) -> u16 {
+                    // Flip all bits of negative numbers and the sign bit of positive ones
+                    // to get the IEEE 754 total order as an unsigned integer:
+                    const SIGN_BIT: $bits = 1 << (8 * mem::size_of::<$t>() - 1);
+                    let bits = self.to_bits();
+                    let bits = if bits & SIGN_BIT != 0 { !bits } else { bits | SIGN_BIT };
+                    bits.digit(index)
+                }
+
+                #[inline(always)]
+                fn radix_cmp(&self, other: &Self) -> Ordering {
+                    self.total_cmp(other)
+                }
+            }
+        )+
+    };
+}
+
+impl_radix_key_for_float! {
+    f32 => u32;
+    f64 => u64;
+}
+
+impl RadixKey for [u8] {
+    const FIXED_DIGITS: Option<usize> = None;
+
+    #[inline(always)]
+    fn num_digits(&self) -> usize {
+        self.len() + 1
+    }
+
+    #[inline(always)]
+    fn digit(&self, index: usize) -> u16 {
+        match self.get(index) {
+            Some(&byte) => byte as u16 + 1,
+            None => 0,
+        }
+    }
+
+    #[inline(always)]
+    fn radix_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+}
+
+macro_rules! impl_radix_key_as_bytes {
+    ($($t:ty;)+) => {
+        $(
+            impl RadixKey for $t {
+                const FIXED_DIGITS: Option<usize> = None;
+
+                #[inline(always)]
+                fn num_digits(&self) -> usize {
+                    self.as_bytes().num_digits()
+                }
+
+                #[inline(always)]
+                fn digit(&self, index: usize) -> u16 {
+                    self.as_bytes().digit(index)
+                }
+
+                #[inline(always)]
+                fn radix_cmp(&self, other: &Self) -> Ordering {
+                    self.as_bytes().cmp(other.as_bytes())
+                }
+            }
+        )+
+    };
+}
+
+/// Lets us treat all byte strings the same.
+trait AsBytes {
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl AsBytes for Vec<u8> {
+    #[inline(always)]
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsBytes for str {
+    #[inline(always)]
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl AsBytes for String {
+    #[inline(always)]
+    fn as_bytes(&self) -> &[u8] {
+        str::as_bytes(self)
+    }
+}
+
+impl_radix_key_as_bytes! {
+    Vec<u8>;
+    str;
+    String;
+}
+
+impl<K: RadixKey + ?Sized> RadixKey for &K {
+    const FIXED_DIGITS: Option<usize> = K::FIXED_DIGITS;
+
+    #[inline(always)]
+    fn num_digits(&self) -> usize {
+        (**self).num_digits()
+    }
+
+    #[inline(always)]
+    fn digit(&self, index: usize) -> u16 {
+        (**self).digit(index)
+    }
+
+    #[inline(always)]
+    fn radix_cmp(&self, other: &Self) -> Ordering {
+        (**self).radix_cmp(*other)
+    }
+}
+
+const fn add_fixed_digits(a: Option<usize>, b: Option<usize>) -> Option<usize> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a + b),
+        _ => None,
+    }
+}
+
+// The digits of a tuple are the digits of its elements one after the other.
+impl<A: RadixKey, B: RadixKey> RadixKey for (A, B) {
+    const FIXED_DIGITS: Option<usize> = add_fixed_digits(A::FIXED_DIGITS, B::FIXED_DIGITS);
+
+    #[inline(always)]
+    fn num_digits(&self) -> usize {
+        self.0.num_digits() + self.1.num_digits()
+    }
+
+    #[inline(always)]
+    fn digit(&self, index: usize) -> u16 {
+        let num_digits_0 = self.0.num_digits();
+        if index < num_digits_0 {
+            self.0.digit(index)
+        } else {
+            self.1.digit(index - num_digits_0)
+        }
+    }
+
+    #[inline(always)]
+    fn radix_cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .radix_cmp(&other.0)
+            .then_with(|| self.1.radix_cmp(&other.1))
+    }
+}
+
+impl<A: RadixKey, B: RadixKey, C: RadixKey> RadixKey for (A, B, C) {
+    const FIXED_DIGITS: Option<usize> = add_fixed_digits(
+        A::FIXED_DIGITS,
+        add_fixed_digits(B::FIXED_DIGITS, C::FIXED_DIGITS),
+    );
+
+    #[inline(always)]
+    fn num_digits(&self) -> usize {
+        self.0.num_digits() + self.1.num_digits() + self.2.num_digits()
+    }
+
+    #[inline(always)]
+    fn digit(&self, index: usize) -> u16 {
+        let num_digits_0 = self.0.num_digits();
+        if index < num_digits_0 {
+            self.0.digit(index)
+        } else {
+            (&self.1, &self.2).digit(index - num_digits_0)
+        }
+    }
+
+    #[inline(always)]
+    fn radix_cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .radix_cmp(&other.0)
+            .then_with(|| self.1.radix_cmp(&other.1))
+            .then_with(|| self.2.radix_cmp(&other.2))
+    }
+}
+
+impl<A: RadixKey, B: RadixKey, C: RadixKey, D: RadixKey> RadixKey for (A, B, C, D) {
+    const FIXED_DIGITS: Option<usize> = add_fixed_digits(
+        A::FIXED_DIGITS,
+        add_fixed_digits(
+            B::FIXED_DIGITS,
+            add_fixed_digits(C::FIXED_DIGITS, D::FIXED_DIGITS),
+        ),
+    );
+
+    #[inline(always)]
+    fn num_digits(&self) -> usize {
+        self.0.num_digits() + self.1.num_digits() + self.2.num_digits() + self.3.num_digits()
+    }
+
+    #[inline(always)]
+    fn digit(&self, index: usize) -> u16 {
+        let num_digits_0 = self.0.num_digits();
+        if index < num_digits_0 {
+            self.0.digit(index)
+        } else {
+            (&self.1, &self.2, &self.3).digit(index - num_digits_0)
+        }
+    }
+
+    #[inline(always)]
+    fn radix_cmp(&self, other: &Self) -> Ordering {
+        self.0
+            .radix_cmp(&other.0)
+            .then_with(|| self.1.radix_cmp(&other.1))
+            .then_with(|| self.2.radix_cmp(&other.2))
+            .then_with(|| self.3.radix_cmp(&other.3))
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Returns the permutation that sorts the keys.
+fn radix_permutation<K: RadixKey>(keys: &[K]) -> Vec<usize> {
+    if let Some(num_digits) = K::FIXED_DIGITS {
+        if num_digits <= 8 {
+            return packed_lsd_radix_permutation(keys, num_digits);
+        }
+    }
+
+    let mut perm: Vec<usize> = (0..keys.len()).collect();
+    let mut scratch = vec![0; keys.len()];
+    match K::FIXED_DIGITS {
+        Some(num_digits) => lsd_radix_sort(&mut perm, &mut scratch, keys, num_digits),
+        None => msd_radix_sort(&mut perm, &mut scratch, keys, 0),
+    }
+    perm
+}
+
+/// Least-significant-digit radix sort of keys with at most 8 digits.
+/// The digits are packed into a `u64` next to the index so that each pass reads memory sequentially.
+fn packed_lsd_radix_permutation<K: RadixKey>(keys: &[K], num_digits: usize) -> Vec<usize> {
+    let mut packed: Vec<(u64, usize)> = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| {
+            let bits = (0..num_digits).fold(0, |bits, digit| (bits << 8) | key.digit(digit) as u64);
+            (bits, i)
+        })
+        .collect();
+    let mut scratch = packed.clone();
+
+    for digit in 0..num_digits {
+        let shift = 8 * digit;
+        let mut offsets = [0; 256];
+        for &(bits, _) in &packed {
+            offsets[(bits >> shift) as u8 as usize] += 1;
+        }
+
+        if offsets.contains(&packed.len()) {
+            continue; // All keys have the same digit here.
+        }
+
+        let mut offset = 0;
+        for bucket in offsets.iter_mut() {
+            let count = *bucket;
+            *bucket = offset;
+            offset += count;
+        }
+
+        for &(bits, i) in &packed {
+            let bucket = &mut offsets[(bits >> shift) as u8 as usize];
+            scratch[*bucket] = (bits, i);
+            *bucket += 1;
+        }
+
+        mem::swap(&mut packed, &mut scratch);
+    }
+
+    packed.into_iter().map(|(_, i)| i).collect()
+}
+
+/// Reorders the slice so that `slice[i]` becomes what was `slice[perm[i]]`.
+///
+/// Unlike `apply_permutation_in_place` this reads each element once and writes it once,
+/// at the cost of a temporary buffer.
+fn gather<T>(slice: &mut [T], perm: &[usize]) {
+    assert_eq!(slice.len(), perm.len());
+    let mut sorted: Vec<T> = Vec::with_capacity(slice.len());
+    unsafe {
+        // No user code runs here, so we don't need to worry about panics.
+        for (dest, &source) in perm.iter().enumerate() {
+            ptr::copy_nonoverlapping(slice.as_ptr().add(source), sorted.as_mut_ptr().add(dest), 1);
+        }
+        // `sorted` keeps length 0, so its elements are not dropped:
+        ptr::copy_nonoverlapping(sorted.as_ptr(), slice.as_mut_ptr(), slice.len());
+    }
+}
+
+/// Least-significant-digit radix sort that moves the elements themselves rather than indices.
+fn lsd_radix_sort_elements<T: RadixKey>(slice: &mut [T], num_digits: usize) {
+    let len = slice.len();
+    let mut scratch: Vec<T> = Vec::with_capacity(len);
+
+    for digit in (0..num_digits).rev() {
+        let mut ends = [0; NUM_BUCKETS];
+        for element in slice.iter() {
+            ends[element.digit(digit) as usize] += 1;
+        }
+
+        if ends.contains(&len) {
+            continue; // All keys have the same digit here.
+        }
+
+        let mut offsets = [0; NUM_BUCKETS];
+        let mut offset = 0;
+        for (bucket, end) in offsets.iter_mut().zip(ends.iter_mut()) {
+            *bucket = offset;
+            offset += *end;
+            *end = offset;
+        }
+
+        unsafe {
+            // `scratch` keeps length 0, so if `digit` panics `slice` is still the only owner of the elements.
+            for element in slice.iter() {
+                let bucket = &mut offsets[element.digit(digit) as usize];
+                assert!(*bucket < len, "RadixKey::digit is not deterministic");
+                ptr::copy_nonoverlapping(element, scratch.as_mut_ptr().add(*bucket), 1);
+                *bucket += 1;
+            }
+
+            // Make sure each slot in `scratch` was written exactly once before handing the elements back:
+            assert!(offsets == ends, "RadixKey::digit is not deterministic");
+            ptr::copy_nonoverlapping(scratch.as_ptr(), slice.as_mut_ptr(), len);
+        }
+    }
+}
+
+/// Least-significant-digit radix sort of the indices in `perm`.
+fn lsd_radix_sort<K: RadixKey>(
+    perm: &mut Vec<usize>,
+    scratch: &mut Vec<usize>,
+    keys: &[K],
+    num_digits: usize,
+) {
+    for digit in (0..num_digits).rev() {
+        let mut offsets = [0; NUM_BUCKETS];
+        for &i in perm.iter() {
+            offsets[keys[i].digit(digit) as usize] += 1;
+        }
+
+        if offsets.contains(&perm.len()) {
+            continue; // All keys have the same digit here.
+        }
+
+        let mut offset = 0;
+        for bucket in offsets.iter_mut() {
+            let count = *bucket;
+            *bucket = offset;
+            offset += count;
+        }
+
+        for &i in perm.iter() {
+            let bucket = &mut offsets[keys[i].digit(digit) as usize];
+            scratch[*bucket] = i;
+            *bucket += 1;
+        }
+
+        mem::swap(perm, scratch);
+    }
+}
+
+/// Most-significant-digit radix sort of the indices in `perm`, which all share their first `depth` digits.
+fn msd_radix_sort<K: RadixKey>(
+    perm: &mut [usize],
+    scratch: &mut [usize],
+    keys: &[K],
+    mut depth: usize,
+) {
+    loop {
+        if perm.len() < RADIX_MIN_LEN {
+            perm.sort_unstable_by(|&a, &b| keys[a].radix_cmp(&keys[b]));
+            return;
+        }
+
+        if keys[perm[0]].num_digits() <= depth {
+            // No key is a prefix of another, so all keys here are equal.
+            return;
+        }
+
+        let mut counts = [0; NUM_BUCKETS];
+        for &i in perm.iter() {
+            counts[keys[i].digit(depth) as usize] += 1;
+        }
+
+        if counts.contains(&perm.len()) {
+            // All keys have the same digit here.
+            depth += 1;
+            continue;
+        }
+
+        let mut offsets = [0; NUM_BUCKETS];
+        let mut offset = 0;
+        for (bucket, &count) in offsets.iter_mut().zip(counts.iter()) {
+            *bucket = offset;
+            offset += count;
+        }
+
+        let scratch = &mut scratch[..perm.len()];
+        for &i in perm.iter() {
+            let bucket = &mut offsets[keys[i].digit(depth) as usize];
+            scratch[*bucket] = i;
+            *bucket += 1;
+        }
+        perm.copy_from_slice(scratch);
+
+        let mut start = 0;
+        for &count in counts.iter() {
+            if 1 < count {
+                let end = start + count;
+                msd_radix_sort(
+                    &mut perm[start..end],
+                    &mut scratch[start..end],
+                    keys,
+                    depth + 1,
+                );
+            }
+            start += count;
+        }
+        return;
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// A [`SubSorter`](trait.SubSorter.html) that radix sorts elements implementing [`RadixKey`](trait.RadixKey.html).
+///
+/// This ignores the compare function, so only use it when that agrees with `RadixKey::radix_cmp`.
+/// Short slices are sorted with `sort_unstable_by`.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<u64> = (0..1000).rev().collect();
+/// dmsort::sort_by_with_sorters(&mut numbers, &mut dmsort::RadixSort, &mut dmsort::RadixSort, |a, b| a.cmp(b));
+/// assert_eq!(numbers, (0..1000).collect::<Vec<_>>());
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RadixSort;
+
+impl<T: RadixKey> SubSorter<T> for RadixSort {
+    fn sort_by<F>(&mut self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        if slice.len() < RADIX_MIN_LEN {
+            slice.sort_unstable_by(compare);
+        } else if let Some(num_digits) = T::FIXED_DIGITS {
+            lsd_radix_sort_elements(slice, num_digits);
+        } else {
+            let perm = radix_permutation(slice);
+            gather(slice, &perm);
+        }
+    }
+}
+
+/// Radix sorts by a key function. The key function is shared with the compare function.
+struct RadixSortByKey<'a, F: 'a> {
+    key: &'a RefCell<F>,
+}
+
+impl<'a, T, K, F> SubSorter<T> for RadixSortByKey<'a, F>
+where
+    K: RadixKey,
+    F: FnMut(&T) -> K,
+{
+    fn sort_by<C>(&mut self, slice: &mut [T], compare: C)
+    where
+        C: FnMut(&T, &T) -> Ordering,
+    {
+        if slice.len() < RADIX_MIN_LEN {
+            slice.sort_unstable_by(compare);
+        } else {
+            let keys: Vec<K> = slice.iter().map(&mut *self.key.borrow_mut()).collect();
+            let perm = radix_permutation(&keys);
+            gather(slice, &perm);
+        }
+    }
+}
+
+/// Sorts the elements with Drop-Merge sort, using radix sort for the out-of-order elements.
+///
+/// For integer keys this is a lot faster than [`sort`](fn.sort.html) when many elements are out of order.
+/// It is also used as the fallback when the input is too disordered for Drop-Merge sort.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, -5);
+/// dmsort::sort_radix(&mut numbers);
+/// assert_eq!(numbers, vec!(-5, 0, 1, 2, 3, 4, 6, 7));
+///
+/// let mut floats : Vec<f32> = vec!(0.0, 1.5, -6.0, 7.0);
+/// dmsort::sort_radix(&mut floats);
+/// assert_eq!(floats, vec!(-6.0, 0.0, 1.5, 7.0));
+/// ```
+pub fn sort_radix<T: RadixKey>(slice: &mut [T]) {
+    sort_by_with_sorters(slice, &mut RadixSort, &mut RadixSort, |a, b| a.radix_cmp(b));
+}
+
+/// Sorts the elements using the given key function, using radix sort for the out-of-order elements.
+///
+/// The key function is called `O(N)` times. See [`sort_radix`](fn.sort_radix.html).
+///
+/// # Examples
+/// ```
+/// let mut words = vec!("dd", "a", "ccc", "bbbb");
+/// dmsort::sort_by_radix_key(&mut words, |w| w.len() as u32);
+/// assert_eq!(words, vec!("a", "dd", "ccc", "bbbb"));
+/// ```
+pub fn sort_by_radix_key<T, K, F>(slice: &mut [T], key: F)
+where
+    K: RadixKey,
+    F: FnMut(&T) -> K,
+{
+    let key = RefCell::new(key);
+    sort_by_with_sorters(
+        slice,
+        &mut RadixSortByKey { key: &key },
+        &mut RadixSortByKey { key: &key },
+        |a, b| {
+            let mut key = key.borrow_mut();
+            let (a, b) = (key(a), key(b));
+            a.radix_cmp(&b)
+        },
+    );
+}
@@ -0,0 +1,232 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+
+use config::DmSortConfig;
+use dmsort::try_sort_columns_move_by;
+use sub_sorter::SortUnstable;
+
+// ----------------------------------------------------------------------------
+
+/// Lets the LNS scan keep a whole stretch of in-order elements at once
+/// instead of comparing them one by one.
+pub(crate) trait RunScan<T> {
+    const ENABLED: bool;
+
+    /// Returns the largest `n` such that `slice[..=n]` is nondecreasing, or zero if `slice` is empty.
+    fn in_order_len(slice: &[T]) -> usize;
+}
+
+/// Compare the elements one by one, for when we only have a compare function.
+pub(crate) struct NoRunScan;
+
+impl<T> RunScan<T> for NoRunScan {
+    const ENABLED: bool = false;
+
+    #[inline(always)]
+    fn in_order_len(_slice: &[T]) -> usize {
+        0
+    }
+}
+
+/// Scan primitives with SIMD.
+pub(crate) struct SimdRunScan;
+
+impl<T: ScanKey> RunScan<T> for SimdRunScan {
+    const ENABLED: bool = true;
+
+    #[inline(always)]
+    fn in_order_len(slice: &[T]) -> usize {
+        T::in_order_len(slice)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A primitive that [`sort_primitives`](fn.sort_primitives.html) can scan for in-order stretches with SIMD.
+///
+/// Implemented for `u32`, `i32`, `u64` and `f32`. `f32` is sorted in IEEE 754 total order,
+/// like [`sort_floats`](fn.sort_floats.html) does.
+pub trait ScanKey: Copy + private::Sealed {
+    #[doc(hidden)]
+    fn scan_cmp(&self, other: &Self) -> Ordering;
+
+    #[doc(hidden)]
+    fn in_order_len(slice: &[Self]) -> usize;
+}
+
+/// How many pairs the scalar scan checks before it looks at the result.
+/// Not stopping at the first descent lets the compiler vectorize the loop.
+const SCALAR_CHUNK: usize = 16;
+
+/// The portable version of `ScanKey::in_order_len`.
+#[inline]
+fn in_order_len_scalar<T, G>(slice: &[T], is_greater: G) -> usize
+where
+    G: Fn(&T, &T) -> bool,
+{
+    // slice[..=i] is in order:
+    let mut i = 0;
+    while i + SCALAR_CHUNK < slice.len() {
+        let any_descent = slice[i..=(i + SCALAR_CHUNK)]
+            .windows(2)
+            .fold(false, |any, pair| any | is_greater(&pair[0], &pair[1]));
+        if any_descent {
+            break;
+        }
+        i += SCALAR_CHUNK;
+    }
+    while i + 1 < slice.len() && !is_greater(&slice[i], &slice[i + 1]) {
+        i += 1;
+    }
+    i
+}
+
+/// Maps the bits of an `f32` to an `i32` with the same order as `f32::total_cmp`.
+#[inline(always)]
+fn f32_total_key(x: f32) -> i32 {
+    let bits = x.to_bits() as i32;
+    bits ^ (((bits >> 31) as u32) >> 1) as i32
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    /// `ScanKey::in_order_len` for 32-bit keys, four at a time.
+    /// `key` maps the loaded bits to `i32`s with the order we want.
+    ///
+    /// SSE2 is part of x86_64, so this is always available.
+    #[inline(always)]
+    pub(super) fn in_order_len_32<T, K, G>(slice: &[T], key: K, is_greater: G) -> usize
+    where
+        K: Fn(__m128i) -> __m128i,
+        G: Fn(&T, &T) -> bool,
+    {
+        debug_assert_eq!(std::mem::size_of::<T>(), 4);
+        let ptr = slice.as_ptr() as *const __m128i;
+        let mut i = 0;
+        // We compare slice[i..(i + 8)] with slice[(i + 1)..(i + 9)]:
+        while i + 9 <= slice.len() {
+            unsafe {
+                let a0 = key(_mm_loadu_si128((ptr as *const T).add(i) as *const __m128i));
+                let b0 = key(_mm_loadu_si128(
+                    (ptr as *const T).add(i + 1) as *const __m128i
+                ));
+                let a1 = key(_mm_loadu_si128(
+                    (ptr as *const T).add(i + 4) as *const __m128i
+                ));
+                let b1 = key(_mm_loadu_si128(
+                    (ptr as *const T).add(i + 5) as *const __m128i
+                ));
+                let descents = _mm_or_si128(_mm_cmpgt_epi32(a0, b0), _mm_cmpgt_epi32(a1, b1));
+                if _mm_movemask_epi8(descents) != 0 {
+                    break;
+                }
+            }
+            i += 8;
+        }
+        while i + 1 < slice.len() && !is_greater(&slice[i], &slice[i + 1]) {
+            i += 1;
+        }
+        i
+    }
+
+    #[inline(always)]
+    pub(super) fn i32_key(x: __m128i) -> __m128i {
+        x
+    }
+
+    #[inline(always)]
+    pub(super) fn u32_key(x: __m128i) -> __m128i {
+        unsafe { _mm_xor_si128(x, _mm_set1_epi32(i32::MIN)) }
+    }
+
+    #[inline(always)]
+    pub(super) fn f32_key(x: __m128i) -> __m128i {
+        // Flip all bits but the sign bit of negative numbers, like `f32_total_key`:
+        unsafe { _mm_xor_si128(x, _mm_srli_epi32(_mm_srai_epi32(x, 31), 1)) }
+    }
+}
+
+macro_rules! impl_scan_key_for_32_bit {
+    ($($t:ty => $sse2_key:ident, $cmp:expr, $is_greater:expr;)+) => {
+        $(
+            impl private::Sealed for $t {}
+
+            impl ScanKey for $t {
+                #[inline(always)]
+                fn scan_cmp(&self, other: &Self) -> Ordering {
+                    $cmp(self, other)
+                }
+
+                #[inline]
+                fn in_order_len(slice: &[Self]) -> usize {
+                    #[cfg(target_arch = "x86_64")]
+                    {
+                        sse2::in_order_len_32(slice, sse2::$sse2_key, $is_greater)
+                    }
+                    #[cfg(not(target_arch = "x86_64"))]
+                    {
+                        in_order_len_scalar(slice, $is_greater)
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_scan_key_for_32_bit! {
+    i32 => i32_key, |a: &i32, b: &i32| a.cmp(b), |a: &i32, b: &i32| a > b;
+    u32 => u32_key, |a: &u32, b: &u32| a.cmp(b), |a: &u32, b: &u32| a > b;
+    f32 => f32_key, |a: &f32, b: &f32| a.total_cmp(b), |a: &f32, b: &f32| f32_total_key(*a) > f32_total_key(*b);
+}
+
+impl private::Sealed for u64 {}
+
+/// SSE2 has no 64-bit compare, so this always uses the scalar scan.
+impl ScanKey for u64 {
+    #[inline(always)]
+    fn scan_cmp(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+
+    #[inline]
+    fn in_order_len(slice: &[Self]) -> usize {
+        in_order_len_scalar(slice, |a, b| a > b)
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Sorts `u32`, `i32`, `u64` or `f32`, checking long in-order stretches with SIMD
+/// instead of comparing the elements one by one.
+///
+/// This gives the same result as [`sort`](fn.sort.html) (or [`sort_floats`](fn.sort_floats.html) for `f32`),
+/// but is faster on nearly-sorted input.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::sort_primitives(&mut numbers);
+/// assert_eq!(numbers, vec!(0, 1, 2, 3, 4, 5, 6, 7));
+/// ```
+pub fn sort_primitives<T: ScanKey>(slice: &mut [T]) {
+    let result = try_sort_columns_move_by(
+        slice,
+        (),
+        &DmSortConfig::default(),
+        0,
+        None,
+        None,
+        SimdRunScan,
+        &mut SortUnstable,
+        &mut SortUnstable,
+        |a, b| a.scan_cmp(b),
+    );
+    debug_assert!(result.is_ok());
+}
@@ -0,0 +1,182 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::{self, Ordering};
+use std::ptr;
+
+use config::DmSortConfig;
+use dedup::KeepDuplicates;
+use dmsort::{find_lns, DmSorter, DropToBuffer};
+use run_scan::NoRunScan;
+use stats::SortStats;
+use sub_sorter::SortUnstable;
+
+// ----------------------------------------------------------------------------
+
+/// Number of elements in `dropped` that are strictly less than `x`.
+fn count_less<T, F>(dropped: &[T], x: &T, compare: &mut F) -> usize
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    dropped
+        .iter()
+        .filter(|d| compare(d, x) == Ordering::Less)
+        .count()
+}
+
+/// Reorders the slice so that `slice[n]` is the element that would be there if the slice was sorted,
+/// everything before it is less or equal, and everything after it is greater or equal.
+fn select_move_by<T, F>(slice: &mut [T], n: usize, mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    let len = slice.len();
+    assert!(
+        n < len,
+        "select_nth: index {} is out of bounds for a slice of length {}",
+        n,
+        len
+    );
+
+    unsafe {
+        let mut s = DmSorter::new(slice);
+
+        if !find_lns(
+            &mut s,
+            &DmSortConfig::default(),
+            &mut SortStats::default(),
+            NoRunScan,
+            &mut DropToBuffer,
+            &mut KeepDuplicates,
+            &mut SortUnstable,
+            &mut compare,
+        ) {
+            s.slice.select_nth_unstable_by(n, |a, b| compare(a, b));
+            return;
+        }
+
+        let num_kept = s.write;
+        let num_dropped = s.dropped.len();
+        if num_dropped == 0 {
+            return; // Already sorted
+        }
+
+        // ------------------------------------------------------------------------
+        // Find the number of kept elements that end up before index n.
+        //
+        // In the sorted order the kept element kept[i] ends up at index
+        // rank(i) = i + (number of dropped elements less than kept[i]).
+        // We want `p`, the first i where rank(i) >= n.
+        // Since i <= rank(i) <= i + num_dropped we only need to search a window of num_dropped elements.
+
+        let mut lo = n.saturating_sub(num_dropped);
+        let mut hi = cmp::min(n, num_kept);
+        let mut rank_of_hi = None;
+
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let rank = mid + count_less(&s.dropped, s.slice.get_unchecked(mid), &mut compare);
+            if rank < n {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+                rank_of_hi = Some(rank);
+            }
+        }
+
+        let p = lo;
+        let pivot_is_kept = p < num_kept
+            && n == rank_of_hi.unwrap_or_else(|| {
+                p + count_less(&s.dropped, s.slice.get_unchecked(p), &mut compare)
+            });
+
+        // ------------------------------------------------------------------------
+        // Partition the dropped elements so that the ones that go before index n come first:
+
+        let num_dropped_before = n - p;
+        if pivot_is_kept {
+            if 0 < num_dropped_before && num_dropped_before < num_dropped {
+                s.dropped
+                    .select_nth_unstable_by(num_dropped_before - 1, |a, b| compare(a, b));
+            }
+        } else {
+            s.dropped
+                .select_nth_unstable_by(num_dropped_before, |a, b| compare(a, b));
+        }
+
+        // ------------------------------------------------------------------------
+        // Move kept[p..] to the end of the slice and put the dropped elements in the gap:
+
+        ptr::copy(
+            s.slice.as_ptr().add(p),
+            s.slice.as_mut_ptr().add(p + num_dropped),
+            num_kept - p,
+        );
+        s.write = p;
+
+        ptr::copy_nonoverlapping(s.dropped.as_ptr(), s.slice.as_mut_ptr().add(p), num_dropped);
+        s.dropped.set_len(0);
+
+        if pivot_is_kept {
+            // kept[p] is now at p + num_dropped, right after the dropped elements:
+            s.slice.swap(n, p + num_dropped);
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------
+
+/// Reorders the slice using the given compare function so that the element at index `n` is at its final sorted position.
+///
+/// All elements before `n` will be less than or equal to it, and all elements after it will be greater or equal.
+/// Like [`sort_by`](fn.sort_by.html) this runs in `O(N + K * log(K))` comparisons
+/// where `K` is the number of elements out of order, but skips the final merge.
+///
+/// Returns a reference to the element at index `n`.
+///
+/// # Panics
+/// Panics when `n >= slice.len()`.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// assert_eq!(*dmsort::select_nth_by(&mut numbers, 2, |a, b| b.cmp(a)), 5);
+/// ```
+pub fn select_nth_by<T, F>(slice: &mut [T], n: usize, compare: F) -> &mut T
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    select_move_by(slice, n, compare);
+    &mut slice[n]
+}
+
+/// Reorders the slice using the given key function so that the element at index `n` is at its final sorted position.
+///
+/// See [`select_nth_by`](fn.select_nth_by.html).
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// assert_eq!(*dmsort::select_nth_by_key(&mut numbers, 0, |x| -x), 7);
+/// ```
+pub fn select_nth_by_key<T, K, F>(slice: &mut [T], n: usize, mut key: F) -> &mut T
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    select_nth_by(slice, n, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Reorders the slice using the Ord trait so that the element at index `n` is at its final sorted position.
+///
+/// See [`select_nth_by`](fn.select_nth_by.html).
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// assert_eq!(*dmsort::select_nth(&mut numbers, 4), 4);
+/// assert!(numbers[..4].iter().all(|&x| x <= 4));
+/// assert!(numbers[5..].iter().all(|&x| x >= 4));
+/// ```
+pub fn select_nth<T: Ord>(slice: &mut [T], n: usize) -> &mut T {
+    select_nth_by(slice, n, |a, b| a.cmp(b))
+}
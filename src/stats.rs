@@ -0,0 +1,61 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use precheck::Precheck;
+
+// ----------------------------------------------------------------------------
+
+/// What happened during a sort, returned by [`sort_by_with_config`](fn.sort_by_with_config.html).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SortStats {
+    /// The number of elements sorted.
+    pub len: usize,
+
+    /// The number of elements that were not part of the in-order subsequence and had to be sorted separately.
+    /// On early-out, this is how many had been dropped before giving up.
+    pub num_dropped: usize,
+
+    /// Set if Drop-Merge sort gave up on the input and used the fallback sort.
+    pub early_out: Option<EarlyOut>,
+
+    /// On early-out, the number of in-order elements that were kept.
+    /// Only the other elements were sorted with the fallback sort, and then merged with these.
+    pub num_salvaged: usize,
+
+    /// Set if `DmSortConfig::precheck` was used, and then tells what algorithm it picked and why.
+    pub precheck: Option<Precheck>,
+
+    /// How many times `DmSortConfig::max_dropped` was reached and the dropped elements were merged
+    /// into the kept elements before continuing (see `MaxDroppedPolicy::Merge`).
+    pub num_flushes: usize,
+}
+
+/// Why Drop-Merge sort gave up on the input and used the fallback sort instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EarlyOut {
+    /// Too many of the elements read since the previous disorder check were dropped.
+    Disorder {
+        /// Which check it was, starting from zero.
+        checkpoint: usize,
+
+        /// How far into the input the check was done.
+        num_read: usize,
+    },
+
+    /// The scan did too much work, e.g. because the input was crafted to make it backtrack a lot.
+    WorkBudget {
+        /// How far into the input the scan had gotten.
+        num_read: usize,
+    },
+
+    /// `DmSortConfig::max_dropped` was reached with `MaxDroppedPolicy::Fallback`.
+    MaxDropped {
+        /// How far into the input the scan had gotten.
+        num_read: usize,
+    },
+
+    /// There was not enough memory for the dropped elements (see [`try_reserve_sort_by`](fn.try_reserve_sort_by.html)).
+    AllocFailure {
+        /// How far into the input the scan had gotten.
+        num_read: usize,
+    },
+}
@@ -0,0 +1,183 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+
+use argsort::PermutationIndex;
+use dmsort::sort_move_by;
+use indirect::move_into_place;
+
+// ----------------------------------------------------------------------------
+
+/// The length of the prefix that all of `keys` have in common.
+fn common_prefix_len<'a, I>(mut keys: I) -> usize
+where
+    I: Iterator<Item = &'a [u8]>,
+{
+    let first = match keys.next() {
+        Some(first) => first,
+        None => return 0,
+    };
+    let mut len = first.len();
+    for key in keys {
+        len = len.min(key.len());
+        // Usually the prefix is shared, which is a fast memcmp to confirm:
+        if first[..len] != key[..len] {
+            len = first.iter().zip(key).take_while(|(a, b)| a == b).count();
+            if len == 0 {
+                break;
+            }
+        }
+    }
+    len
+}
+
+/// The cached start of a key, and the index of its element.
+#[derive(Clone, Copy)]
+struct CachedKey<I> {
+    /// The first 8 bytes of the key as a big-endian number, padded with zeros.
+    prefix: u64,
+
+    /// The length of the key, or 9 if it is longer than the prefix.
+    len: u32,
+
+    index: I,
+}
+
+impl<I> CachedKey<I> {
+    #[inline]
+    fn new(key: &[u8], index: I) -> Self {
+        let mut bytes = [0; 8];
+        let len = key.len().min(8);
+        bytes[..len].copy_from_slice(&key[..len]);
+        CachedKey {
+            prefix: u64::from_be_bytes(bytes),
+            len: key.len().min(9) as u32,
+            index,
+        }
+    }
+
+    /// Compares the keys as far as we can without looking at them.
+    ///
+    /// If `a < b` then `a.prefix <= b.prefix`, and when the prefixes are equal the shorter key is the smaller one.
+    /// So only keys that are both longer than 8 bytes can compare `Equal` here without being equal.
+    #[inline(always)]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.prefix
+            .cmp(&other.prefix)
+            .then(self.len.cmp(&other.len))
+    }
+}
+
+/// Caches the keys of all elements, after the prefix `shared` which they must all start with.
+/// Returns `None` if one of them does not.
+fn cache_keys<T, K, I, F>(slice: &[T], shared: &[u8], key: &mut F) -> Option<Vec<CachedKey<I>>>
+where
+    K: AsRef<[u8]> + ?Sized,
+    I: PermutationIndex,
+    F: for<'a> FnMut(&'a T) -> &'a K,
+{
+    let skip = shared.len();
+    let mut cached = Vec::with_capacity(slice.len());
+    for (i, x) in slice.iter().enumerate() {
+        let key = key(x).as_ref();
+        if key.len() < skip || key[..skip] != *shared {
+            return None;
+        }
+        cached.push(CachedKey::new(&key[skip..], I::from_usize(i)));
+    }
+    Some(cached)
+}
+
+/// Sorts `slice` by sorting `CachedKey`s, which is what Drop-Merge sort keeps and drops,
+/// and then moves the elements into place.
+fn sort_by_cached_key<T, K, I, F>(slice: &mut [T], mut key: F)
+where
+    K: AsRef<[u8]> + ?Sized,
+    I: PermutationIndex,
+    F: for<'a> FnMut(&'a T) -> &'a K,
+{
+    if slice.is_empty() {
+        return;
+    }
+
+    let mut perm: Vec<I> = {
+        let slice: &[T] = slice;
+
+        // We skip the prefix that all keys share, so that the cached prefixes differ, e.g. for zero-padded numbers.
+        // In nearly-sorted input that is what the first and last keys share, so we guess that,
+        // and check it while caching the keys:
+        let first = key(&slice[0]).as_ref();
+        let last = key(&slice[slice.len() - 1]).as_ref();
+        let guess = common_prefix_len([first, last].iter().cloned());
+        let (skip, mut cached): (usize, Vec<CachedKey<I>>) =
+            match cache_keys(slice, &first[..guess], &mut key) {
+                Some(cached) => (guess, cached),
+                None => {
+                    let shared = common_prefix_len(slice.iter().map(|x| key(x).as_ref()));
+                    (
+                        shared,
+                        cache_keys(slice, &first[..shared], &mut key).unwrap(),
+                    )
+                }
+            };
+
+        // The elements stay put while `key` runs, so a panic leaves them all in the slice.
+        sort_move_by(&mut cached, |a, b| {
+            a.cmp(b).then_with(|| {
+                if a.len <= 8 {
+                    return Ordering::Equal;
+                }
+                let a = key(&slice[a.index.to_usize()]).as_ref();
+                let b = key(&slice[b.index.to_usize()]).as_ref();
+                a[skip + 8..].cmp(&b[skip + 8..])
+            })
+        });
+
+        cached.into_iter().map(|cached| cached.index).collect()
+    };
+    move_into_place(&mut perm, slice);
+}
+
+/// Sorts the elements by a string or byte slice key, comparing cached prefixes of the keys.
+///
+/// Drop-Merge sort compares each element at least once, and strings with long common prefixes,
+/// such as paths or zero-padded numbers, are slow to compare.
+/// This first skips the prefix that all keys share, and then keeps the next 8 bytes of each key as a `u64`,
+/// together with its length and the index of the element. Drop-Merge sort runs over these,
+/// and the keys are only compared when they are longer than 8 bytes and their cached prefixes are equal.
+/// Finally each element is moved into its sorted place once.
+///
+/// Keys are compared byte by byte, which for `str` is the same as comparing them with `Ord`.
+/// The extra memory is 16 bytes per element.
+///
+/// # Examples
+/// ```
+/// let mut people = vec![("Bob", 32), ("Alice", 27), ("Carol", 45)];
+/// dmsort::sort_by_str_key(&mut people, |p| p.0);
+/// assert_eq!(people, vec![("Alice", 27), ("Bob", 32), ("Carol", 45)]);
+/// ```
+pub fn sort_by_str_key<T, K, F>(slice: &mut [T], key: F)
+where
+    K: AsRef<[u8]> + ?Sized,
+    F: for<'a> FnMut(&'a T) -> &'a K,
+{
+    if slice.len() <= u32::MAX as usize {
+        sort_by_cached_key::<T, K, u32, F>(slice, key);
+    } else {
+        sort_by_cached_key::<T, K, usize, F>(slice, key);
+    }
+}
+
+/// Sorts strings or byte slices, comparing cached prefixes first.
+///
+/// See [`sort_by_str_key`](fn.sort_by_str_key.html).
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<String> = vec!(0, 1, 6, 7, 2, 3, 4, 5).iter().map(|x| format!("{:0100}", x)).collect();
+/// dmsort::sort_strings(&mut numbers);
+/// assert!(numbers.iter().map(|x| x.parse::<i32>().unwrap()).eq(0..8));
+/// ```
+pub fn sort_strings<T: AsRef<[u8]>>(slice: &mut [T]) {
+    sort_by_str_key(slice, |x| x);
+}
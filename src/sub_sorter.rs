@@ -0,0 +1,139 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+use std::mem;
+use std::ptr;
+
+// ----------------------------------------------------------------------------
+
+/// A sorting algorithm that Drop-Merge sort can delegate to.
+///
+/// Drop-Merge sort uses another sorting algorithm in two places:
+/// to sort the dropped (out-of-order) elements, and as a fallback for the whole slice
+/// when the input turns out to be too disordered.
+/// Each can be configured separately with [`sort_by_with_sorters`](fn.sort_by_with_sorters.html).
+///
+/// An implementation must leave the slice holding the same elements even if `compare` panics,
+/// which any implementation that only uses safe code does.
+///
+/// # Examples
+/// ```
+/// use std::cmp::Ordering;
+///
+/// /// Sorts u32 by their bits, ignoring the compare function.
+/// struct RadixSort;
+///
+/// impl dmsort::SubSorter<u32> for RadixSort {
+///     fn sort_by<F>(&mut self, slice: &mut [u32], _compare: F)
+///     where
+///         F: FnMut(&u32, &u32) -> Ordering,
+///     {
+///         for shift in (0..32).step_by(8) {
+///             let mut buckets = vec![vec![]; 256];
+///             for &x in slice.iter() {
+///                 buckets[((x >> shift) & 0xff) as usize].push(x);
+///             }
+///             for (dest, x) in slice.iter_mut().zip(buckets.into_iter().flatten()) {
+///                 *dest = x;
+///             }
+///         }
+///     }
+/// }
+///
+/// let mut numbers : Vec<u32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// dmsort::sort_by_with_sorters(&mut numbers, &mut RadixSort, &mut dmsort::SortUnstable, |a, b| a.cmp(b));
+/// assert_eq!(numbers, vec!(0, 1, 2, 3, 4, 5, 6, 7));
+/// ```
+pub trait SubSorter<T> {
+    fn sort_by<F>(&mut self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering;
+}
+
+/// [`slice::sort_unstable_by`](https://doc.rust-lang.org/std/primitive.slice.html#method.sort_unstable_by).
+/// This is the default for both the dropped elements and the fallback.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SortUnstable;
+
+impl<T> SubSorter<T> for SortUnstable {
+    #[inline(always)]
+    fn sort_by<F>(&mut self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        slice.sort_unstable_by(compare);
+    }
+}
+
+/// [`slice::sort_by`](https://doc.rust-lang.org/std/primitive.slice.html#method.sort_by), a stable merge sort.
+///
+/// Note that Drop-Merge sort is not stable even when this is used.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SortStable;
+
+impl<T> SubSorter<T> for SortStable {
+    #[inline(always)]
+    fn sort_by<F>(&mut self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        slice.sort_by(compare);
+    }
+}
+
+/// Binary insertion sort. Does `O(K * log(K))` comparisons but `O(K²)` moves,
+/// so only use this when there are very few dropped elements.
+///
+/// Elements that are already in order only cost one comparison.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InsertionSort;
+
+impl<T> SubSorter<T> for InsertionSort {
+    fn sort_by<F>(&mut self, slice: &mut [T], compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        binary_insertion_sort_by(slice, compare);
+    }
+}
+
+/// If `compare` panics, this puts the element being inserted into the hole.
+struct InsertionHole<T> {
+    element: mem::ManuallyDrop<T>,
+    hole: *mut T,
+}
+
+impl<T> Drop for InsertionHole<T> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::copy_nonoverlapping(&*self.element, self.hole, 1);
+        }
+    }
+}
+
+pub(crate) fn binary_insertion_sort_by<T, F>(slice: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    for i in 1..slice.len() {
+        unsafe {
+            if compare(slice.get_unchecked(i), slice.get_unchecked(i - 1)) != Ordering::Less {
+                continue; // Already in order.
+            }
+
+            // Take out slice[i], leaving a hole which `InsertionHole` fills if we panic:
+            let ptr = slice.as_mut_ptr();
+            let mut hole = InsertionHole {
+                element: mem::ManuallyDrop::new(ptr::read(ptr.add(i))),
+                hole: ptr.add(i),
+            };
+
+            let insert_at =
+                slice[..i - 1].partition_point(|x| compare(x, &hole.element) != Ordering::Greater);
+
+            ptr::copy(ptr.add(insert_at), ptr.add(insert_at + 1), i - insert_at);
+            hole.hole = ptr.add(insert_at);
+            // `hole` is dropped here, which moves the element into place.
+        }
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::TryReserveError;
+
+use config::DmSortConfig;
+use dmsort::try_sort_columns_move_by;
+use run_scan::NoRunScan;
+use sub_sorter::{SortUnstable, SubSorter};
+
+// ----------------------------------------------------------------------------
+
+/// Heapsort, which gives up as soon as the compare function of `try_sort_by` has failed.
+///
+/// Once it has failed the compare function answers `Equal` to everything, contradicting its earlier answers.
+/// `sort_unstable_by` may panic when it notices that, but heapsort only ever swaps elements, so it can't go wrong.
+struct HeapSortUntilError<'a>(&'a Cell<bool>);
+
+impl<'a, T> SubSorter<T> for HeapSortUntilError<'a> {
+    fn sort_by<F>(&mut self, slice: &mut [T], mut compare: F)
+    where
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let failed = self.0;
+        for node in (0..slice.len() / 2).rev() {
+            if failed.get() {
+                return;
+            }
+            sift_down(slice, node, &mut compare);
+        }
+        for end in (1..slice.len()).rev() {
+            if failed.get() {
+                return;
+            }
+            slice.swap(0, end);
+            sift_down(&mut slice[..end], 0, &mut compare);
+        }
+    }
+}
+
+/// Move `slice[node]` down the max-heap `slice` until it is no smaller than its children.
+fn sift_down<T, F>(slice: &mut [T], mut node: usize, compare: &mut F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    loop {
+        let mut child = 2 * node + 1;
+        if child >= slice.len() {
+            return;
+        }
+        if child + 1 < slice.len() && compare(&slice[child], &slice[child + 1]) == Ordering::Less {
+            child += 1;
+        }
+        if compare(&slice[node], &slice[child]) != Ordering::Less {
+            return;
+        }
+        slice.swap(node, child);
+        node = child;
+    }
+}
+
+/// Sorts the elements using a compare function that can fail.
+///
+/// On the first error the sort stops and the error is returned.
+/// The slice then holds all the original elements, but in an unspecified order.
+/// The compare function is not called again after it has failed.
+///
+/// The out-of-order elements are sorted with heapsort, which is also used
+/// if the input turns out to be too disordered for Drop-Merge sort.
+/// This is slower than the `sort_unstable_by` that [`sort_by`](fn.sort_by.html) uses,
+/// but unlike it heapsort can be stopped at any time.
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<&str> = vec!("0", "1", "6", "7", "2", "3", "4", "5");
+/// let result = dmsort::try_sort_by(&mut numbers, |a, b| {
+///     Ok::<_, std::num::ParseIntError>(a.parse::<i32>()?.cmp(&b.parse::<i32>()?))
+/// });
+/// assert_eq!(result, Ok(()));
+/// assert_eq!(numbers, vec!("0", "1", "2", "3", "4", "5", "6", "7"));
+///
+/// let mut numbers : Vec<&str> = vec!("0", "1", "six", "7", "2", "3", "4", "5");
+/// let result = dmsort::try_sort_by(&mut numbers, |a, b| {
+///     Ok::<_, std::num::ParseIntError>(a.parse::<i32>()?.cmp(&b.parse::<i32>()?))
+/// });
+/// assert!(result.is_err());
+/// assert_eq!(numbers.len(), 8);
+/// ```
+pub fn try_sort_by<T, E, F>(slice: &mut [T], mut compare: F) -> Result<(), E>
+where
+    F: FnMut(&T, &T) -> Result<Ordering, E>,
+{
+    // The first error is kept, and then the sort stops as soon as it notices `failed`:
+    let failed = Cell::new(false);
+    let mut error = None;
+    let result = try_sort_columns_move_by(
+        slice,
+        (),
+        &DmSortConfig::default(),
+        0,
+        None,
+        Some(&failed),
+        NoRunScan,
+        &mut HeapSortUntilError(&failed),
+        &mut HeapSortUntilError(&failed),
+        |a, b| {
+            if failed.get() {
+                return Ordering::Equal;
+            }
+            compare(a, b).unwrap_or_else(|err| {
+                error = Some(err);
+                failed.set(true);
+                Ordering::Equal
+            })
+        },
+    );
+    debug_assert!(result.is_ok());
+    match error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Sorts the elements using a key function that can fail.
+///
+/// See [`try_sort_by`](fn.try_sort_by.html).
+///
+/// # Examples
+/// ```
+/// let mut numbers : Vec<&str> = vec!("0", "1", "6", "7", "2", "3", "4", "5");
+/// assert_eq!(dmsort::try_sort_by_key(&mut numbers, |x| x.parse::<i32>()), Ok(()));
+/// assert_eq!(numbers, vec!("0", "1", "2", "3", "4", "5", "6", "7"));
+/// ```
+pub fn try_sort_by_key<T, K, E, F>(slice: &mut [T], mut key: F) -> Result<(), E>
+where
+    K: Ord,
+    F: FnMut(&T) -> Result<K, E>,
+{
+    try_sort_by(slice, |a, b| Ok(key(a)?.cmp(&key(b)?)))
+}
+
+// ----------------------------------------------------------------------------
+
+/// What [`try_reserve_sort_by`](fn.try_reserve_sort_by.html) should do when there is not enough memory
+/// for the out-of-order elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AllocPolicy {
+    /// Sort everything in place with `sort_unstable_by` instead, which needs no extra memory.
+    Fallback,
+
+    /// Return the `TryReserveError`. The slice keeps all its elements, but in an unspecified order.
+    Error,
+}
+
+/// Sorts the elements using the given compare function,
+/// without aborting the process if there is not enough memory for the out-of-order elements.
+///
+/// Drop-Merge sort moves the out-of-order elements into a separate buffer.
+/// [`sort_by`](fn.sort_by.html) grows it with `Vec::reserve`, which aborts on allocation failure.
+/// This uses `Vec::try_reserve` instead, and on failure does what `alloc_policy` says.
+/// With `AllocPolicy::Fallback` this always returns `Ok`.
+///
+/// # Examples
+/// ```
+/// use dmsort::AllocPolicy;
+/// let mut numbers : Vec<i32> = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// let result = dmsort::try_reserve_sort_by(&mut numbers, AllocPolicy::Error, |a, b| b.cmp(a));
+/// assert!(result.is_ok());
+/// assert_eq!(numbers, vec!(7, 6, 5, 4, 3, 2, 1, 0));
+/// ```
+pub fn try_reserve_sort_by<T, F>(
+    slice: &mut [T],
+    alloc_policy: AllocPolicy,
+    compare: F,
+) -> Result<(), TryReserveError>
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    try_sort_columns_move_by(
+        slice,
+        (),
+        &DmSortConfig::default(),
+        0,
+        Some(alloc_policy),
+        None,
+        NoRunScan,
+        &mut SortUnstable,
+        &mut SortUnstable,
+        compare,
+    )
+    .map(|_stats| ())
+}
+
+/// Sorts the elements using the given key function, without aborting if out of memory.
+///
+/// See [`try_reserve_sort_by`](fn.try_reserve_sort_by.html).
+pub fn try_reserve_sort_by_key<T, K, F>(
+    slice: &mut [T],
+    alloc_policy: AllocPolicy,
+    mut key: F,
+) -> Result<(), TryReserveError>
+where
+    K: Ord,
+    F: FnMut(&T) -> K,
+{
+    try_reserve_sort_by(slice, alloc_policy, |a, b| key(a).cmp(&key(b)))
+}
+
+/// Sorts the elements using the Ord trait, without aborting if out of memory.
+///
+/// See [`try_reserve_sort_by`](fn.try_reserve_sort_by.html).
+pub fn try_reserve_sort<T: Ord>(
+    slice: &mut [T],
+    alloc_policy: AllocPolicy,
+) -> Result<(), TryReserveError> {
+    try_reserve_sort_by(slice, alloc_policy, |a, b| a.cmp(b))
+}
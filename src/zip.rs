@@ -0,0 +1,362 @@
+// Copyright (c) 2017 Emil Ernerfeldt
+
+use std::cmp::Ordering;
+use std::ptr;
+
+use config::DmSortConfig;
+use dmsort::{sort_columns_move_by, unsafe_copy, unsafe_copy_block, unsafe_push};
+use sub_sorter::SortUnstable;
+
+// ----------------------------------------------------------------------------
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// A mutable slice or `Vec` that can be sorted along with the keys in [`sort_zip`](fn.sort_zip.html).
+pub trait Column: private::Sealed {
+    type Item;
+
+    #[doc(hidden)]
+    fn as_mut_slice(&mut self) -> &mut [Self::Item];
+}
+
+impl<T> private::Sealed for &mut [T] {}
+
+impl<T> Column for &mut [T] {
+    type Item = T;
+
+    #[inline(always)]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+impl<T> private::Sealed for &mut Vec<T> {}
+
+impl<T> Column for &mut Vec<T> {
+    type Item = T;
+
+    #[inline(always)]
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self
+    }
+}
+
+/// One or more columns that are sorted along with the keys in [`sort_zip`](fn.sort_zip.html).
+///
+/// This is implemented for single columns (`&mut [T]` and `&mut Vec<T>`) and for tuples of up to six columns.
+/// All columns must have the same length as the keys.
+///
+/// The methods mirror the moves Drop-Merge sort does on the keys. They are an implementation detail.
+pub trait SortColumns: private::Sealed {
+    #[doc(hidden)]
+    type Dropped: Default;
+
+    #[doc(hidden)]
+    const NUM_COLUMNS: usize;
+
+    #[doc(hidden)]
+    fn assert_len(&mut self, len: usize);
+
+    #[doc(hidden)]
+    unsafe fn copy(&mut self, source: usize, dest: usize);
+
+    #[doc(hidden)]
+    unsafe fn copy_block(&mut self, source: usize, dest: usize, count: usize);
+
+    #[doc(hidden)]
+    unsafe fn push_dropped(&mut self, dropped: &mut Self::Dropped, index: usize);
+
+    #[doc(hidden)]
+    unsafe fn append_dropped(&mut self, dropped: &mut Self::Dropped, index: usize, count: usize);
+
+    #[doc(hidden)]
+    unsafe fn truncate_dropped(dropped: &mut Self::Dropped, len: usize);
+
+    #[doc(hidden)]
+    unsafe fn pop_dropped(&mut self, dropped: &mut Self::Dropped, dest: usize);
+
+    #[doc(hidden)]
+    unsafe fn restore_dropped(&mut self, dropped: &mut Self::Dropped, dest: usize);
+
+    #[doc(hidden)]
+    fn swap(&mut self, a: usize, b: usize);
+
+    #[doc(hidden)]
+    fn dropped_len(dropped: &Self::Dropped) -> usize;
+
+    #[doc(hidden)]
+    fn swap_dropped(dropped: &mut Self::Dropped, a: usize, b: usize);
+}
+
+// No companion columns: this is what plain `sort` uses, so all of these must compile down to nothing.
+impl private::Sealed for () {}
+
+impl SortColumns for () {
+    type Dropped = ();
+    const NUM_COLUMNS: usize = 0;
+
+    #[inline(always)]
+    fn assert_len(&mut self, _len: usize) {}
+    #[inline(always)]
+    unsafe fn copy(&mut self, _source: usize, _dest: usize) {}
+    #[inline(always)]
+    unsafe fn copy_block(&mut self, _source: usize, _dest: usize, _count: usize) {}
+    #[inline(always)]
+    unsafe fn push_dropped(&mut self, _dropped: &mut (), _index: usize) {}
+    #[inline(always)]
+    unsafe fn append_dropped(&mut self, _dropped: &mut (), _index: usize, _count: usize) {}
+    #[inline(always)]
+    unsafe fn truncate_dropped(_dropped: &mut (), _len: usize) {}
+    #[inline(always)]
+    unsafe fn pop_dropped(&mut self, _dropped: &mut (), _dest: usize) {}
+    #[inline(always)]
+    unsafe fn restore_dropped(&mut self, _dropped: &mut (), _dest: usize) {}
+    #[inline(always)]
+    fn swap(&mut self, _a: usize, _b: usize) {}
+    #[inline(always)]
+    fn dropped_len(_dropped: &()) -> usize {
+        0
+    }
+    #[inline(always)]
+    fn swap_dropped(_dropped: &mut (), _a: usize, _b: usize) {}
+}
+
+// A single column. These do the same bit-copying as DmSorter does on the keys.
+impl<C: Column> SortColumns for C {
+    type Dropped = Vec<C::Item>;
+    const NUM_COLUMNS: usize = 1;
+
+    fn assert_len(&mut self, len: usize) {
+        assert_eq!(
+            self.as_mut_slice().len(),
+            len,
+            "All columns must have the same length as the keys"
+        );
+    }
+
+    #[inline(always)]
+    unsafe fn copy(&mut self, source: usize, dest: usize) {
+        unsafe_copy(self.as_mut_slice(), source, dest);
+    }
+
+    #[inline(always)]
+    unsafe fn copy_block(&mut self, source: usize, dest: usize, count: usize) {
+        unsafe_copy_block(self.as_mut_slice(), source, dest, count);
+    }
+
+    #[inline(always)]
+    unsafe fn push_dropped(&mut self, dropped: &mut Self::Dropped, index: usize) {
+        unsafe_push(dropped, self.as_mut_slice().get_unchecked(index));
+    }
+
+    #[inline(always)]
+    unsafe fn append_dropped(&mut self, dropped: &mut Self::Dropped, index: usize, count: usize) {
+        let old_len = dropped.len();
+        dropped.reserve(count);
+        ptr::copy_nonoverlapping(
+            self.as_mut_slice().as_ptr().add(index),
+            dropped.as_mut_ptr().add(old_len),
+            count,
+        );
+        dropped.set_len(old_len + count);
+    }
+
+    #[inline(always)]
+    unsafe fn truncate_dropped(dropped: &mut Self::Dropped, len: usize) {
+        dropped.set_len(len);
+    }
+
+    #[inline(always)]
+    unsafe fn pop_dropped(&mut self, dropped: &mut Self::Dropped, dest: usize) {
+        let new_len = dropped.len() - 1;
+        ptr::copy_nonoverlapping(
+            dropped.get_unchecked(new_len),
+            self.as_mut_slice().get_unchecked_mut(dest),
+            1,
+        );
+        dropped.set_len(new_len);
+    }
+
+    unsafe fn restore_dropped(&mut self, dropped: &mut Self::Dropped, dest: usize) {
+        ptr::copy_nonoverlapping(
+            dropped.as_ptr(),
+            self.as_mut_slice().as_mut_ptr().add(dest),
+            dropped.len(),
+        );
+        dropped.set_len(0);
+    }
+
+    #[inline(always)]
+    fn swap(&mut self, a: usize, b: usize) {
+        <[C::Item]>::swap(self.as_mut_slice(), a, b);
+    }
+
+    #[inline(always)]
+    fn dropped_len(dropped: &Self::Dropped) -> usize {
+        dropped.len()
+    }
+
+    #[inline(always)]
+    fn swap_dropped(dropped: &mut Self::Dropped, a: usize, b: usize) {
+        <[C::Item]>::swap(dropped, a, b);
+    }
+}
+
+macro_rules! impl_sort_columns_for_tuple {
+    ($num_columns:expr; $($name:ident : $index:tt),+) => {
+        impl<$($name: Column),+> private::Sealed for ($($name,)+) {}
+
+        impl<$($name: Column),+> SortColumns for ($($name,)+) {
+            type Dropped = ($(<$name as SortColumns>::Dropped,)+);
+            const NUM_COLUMNS: usize = $num_columns;
+
+            fn assert_len(&mut self, len: usize) {
+                $(SortColumns::assert_len(&mut self.$index, len);)+
+            }
+
+            #[inline(always)]
+            unsafe fn copy(&mut self, source: usize, dest: usize) {
+                $(SortColumns::copy(&mut self.$index, source, dest);)+
+            }
+
+            #[inline(always)]
+            unsafe fn copy_block(&mut self, source: usize, dest: usize, count: usize) {
+                $(SortColumns::copy_block(&mut self.$index, source, dest, count);)+
+            }
+
+            #[inline(always)]
+            unsafe fn push_dropped(&mut self, dropped: &mut Self::Dropped, index: usize) {
+                $(SortColumns::push_dropped(&mut self.$index, &mut dropped.$index, index);)+
+            }
+
+            #[inline(always)]
+            unsafe fn append_dropped(&mut self, dropped: &mut Self::Dropped, index: usize, count: usize) {
+                $(SortColumns::append_dropped(&mut self.$index, &mut dropped.$index, index, count);)+
+            }
+
+            #[inline(always)]
+            unsafe fn truncate_dropped(dropped: &mut Self::Dropped, len: usize) {
+                $(<$name as SortColumns>::truncate_dropped(&mut dropped.$index, len);)+
+            }
+
+            #[inline(always)]
+            unsafe fn pop_dropped(&mut self, dropped: &mut Self::Dropped, dest: usize) {
+                $(SortColumns::pop_dropped(&mut self.$index, &mut dropped.$index, dest);)+
+            }
+
+            unsafe fn restore_dropped(&mut self, dropped: &mut Self::Dropped, dest: usize) {
+                $(SortColumns::restore_dropped(&mut self.$index, &mut dropped.$index, dest);)+
+            }
+
+            #[inline(always)]
+            fn swap(&mut self, a: usize, b: usize) {
+                $(SortColumns::swap(&mut self.$index, a, b);)+
+            }
+
+            #[inline(always)]
+            fn dropped_len(dropped: &Self::Dropped) -> usize {
+                (dropped.0).len()
+            }
+
+            #[inline(always)]
+            fn swap_dropped(dropped: &mut Self::Dropped, a: usize, b: usize) {
+                $(<$name as SortColumns>::swap_dropped(&mut dropped.$index, a, b);)+
+            }
+        }
+    };
+}
+
+impl_sort_columns_for_tuple!(1; A: 0);
+impl_sort_columns_for_tuple!(2; A: 0, B: 1);
+impl_sort_columns_for_tuple!(3; A: 0, B: 1, C: 2);
+impl_sort_columns_for_tuple!(4; A: 0, B: 1, C: 2, D: 3);
+impl_sort_columns_for_tuple!(5; A: 0, B: 1, C: 2, D: 3, E: 4);
+impl_sort_columns_for_tuple!(6; A: 0, B: 1, C: 2, D: 3, E: 4, F: 5);
+
+// ----------------------------------------------------------------------------
+
+/// Sorts the keys using the given compare function, and reorders the companion columns the same way.
+///
+/// `columns` is a single `&mut [T]` / `&mut Vec<T>` or a tuple of them, each as long as `keys`.
+/// Drop-Merge sort runs on the keys, and every element it moves is moved in the columns as well.
+/// If the compare function panics, all columns are left with the same (partially sorted) permutation.
+///
+/// # Panics
+/// Panics if a column does not have the same length as `keys`.
+///
+/// # Examples
+/// ```
+/// let mut depths = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// let mut names = vec!("a", "b", "g", "h", "c", "d", "e", "f");
+/// dmsort::sort_zip_by(&mut depths, &mut names, |a, b| b.cmp(a));
+/// assert_eq!(depths, vec!(7, 6, 5, 4, 3, 2, 1, 0));
+/// assert_eq!(names, vec!("h", "g", "f", "e", "d", "c", "b", "a"));
+/// ```
+pub fn sort_zip_by<K, C, F>(keys: &mut [K], columns: C, compare: F)
+where
+    C: SortColumns,
+    F: FnMut(&K, &K) -> Ordering,
+{
+    sort_columns_move_by(
+        keys,
+        columns,
+        &DmSortConfig::default(),
+        &mut SortUnstable,
+        &mut SortUnstable,
+        compare,
+    );
+}
+
+/// Sorts the keys using the given key function, and reorders the companion columns the same way.
+///
+/// See [`sort_zip_by`](fn.sort_zip_by.html).
+///
+/// # Examples
+/// ```
+/// let mut depths = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// let mut ids = vec!(10, 11, 16, 17, 12, 13, 14, 15);
+/// dmsort::sort_zip_by_key(&mut depths, &mut ids, |x| -x);
+/// assert_eq!(ids, vec!(17, 16, 15, 14, 13, 12, 11, 10));
+/// ```
+pub fn sort_zip_by_key<K, C, KK, F>(keys: &mut [K], columns: C, mut key: F)
+where
+    C: SortColumns,
+    KK: Ord,
+    F: FnMut(&K) -> KK,
+{
+    sort_columns_move_by(
+        keys,
+        columns,
+        &DmSortConfig::default(),
+        &mut SortUnstable,
+        &mut SortUnstable,
+        |a, b| key(a).cmp(&key(b)),
+    );
+}
+
+/// Sorts the keys using the Ord trait, and reorders the companion columns the same way.
+///
+/// See [`sort_zip_by`](fn.sort_zip_by.html).
+///
+/// # Examples
+/// ```
+/// let mut keys = vec!(0, 1, 6, 7, 2, 3, 4, 5);
+/// let mut positions = vec!([0.0; 3], [1.0; 3], [6.0; 3], [7.0; 3], [2.0; 3], [3.0; 3], [4.0; 3], [5.0; 3]);
+/// let mut ids = vec!(10, 11, 16, 17, 12, 13, 14, 15);
+/// dmsort::sort_zip(&mut keys, (&mut positions, &mut ids));
+/// assert_eq!(keys, vec!(0, 1, 2, 3, 4, 5, 6, 7));
+/// assert_eq!(positions[2], [2.0; 3]);
+/// assert_eq!(ids, vec!(10, 11, 12, 13, 14, 15, 16, 17));
+/// ```
+pub fn sort_zip<K: Ord, C: SortColumns>(keys: &mut [K], columns: C) {
+    sort_columns_move_by(
+        keys,
+        columns,
+        &DmSortConfig::default(),
+        &mut SortUnstable,
+        &mut SortUnstable,
+        |a, b| a.cmp(b),
+    );
+}
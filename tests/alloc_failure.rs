@@ -0,0 +1,176 @@
+extern crate dmsort;
+extern crate rand;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use dmsort::AllocPolicy;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Fails all allocations bigger than `MAX_ALLOC_SIZE` bytes on the current thread.
+struct LimitedAlloc;
+
+thread_local! {
+    static MAX_ALLOC_SIZE: Cell<usize> = const { Cell::new(usize::MAX) };
+    static LARGEST_ALLOC_SIZE: Cell<usize> = const { Cell::new(0) };
+}
+
+fn max_alloc_size() -> usize {
+    MAX_ALLOC_SIZE
+        .try_with(|max| max.get())
+        .unwrap_or(usize::MAX)
+}
+
+fn record_alloc_size(size: usize) {
+    let _ = LARGEST_ALLOC_SIZE.try_with(|largest| largest.set(largest.get().max(size)));
+}
+
+unsafe impl GlobalAlloc for LimitedAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() > max_alloc_size() {
+            std::ptr::null_mut()
+        } else {
+            record_alloc_size(layout.size());
+            System.alloc(layout)
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if new_size > max_alloc_size() {
+            std::ptr::null_mut()
+        } else {
+            record_alloc_size(new_size);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: LimitedAlloc = LimitedAlloc;
+
+/// Also resets what `largest_alloc_size` returns.
+fn with_max_alloc_size<R>(max: usize, f: impl FnOnce() -> R) -> R {
+    LARGEST_ALLOC_SIZE.with(|largest| largest.set(0));
+    MAX_ALLOC_SIZE.with(|m| m.set(max));
+    let result = f();
+    MAX_ALLOC_SIZE.with(|m| m.set(usize::MAX));
+    result
+}
+
+/// The largest allocation made on the current thread during the last `with_max_alloc_size`.
+fn largest_alloc_size() -> usize {
+    LARGEST_ALLOC_SIZE.with(|largest| largest.get())
+}
+
+/// Returns a mostly-sorted array with `disorder_factor` fraction of elements with random values.
+fn generate_strings(rng: &mut StdRng, length: usize, disorder_factor: f32) -> Vec<String> {
+    (0..length)
+        .map(|i| {
+            if rng.gen::<f32>() < disorder_factor {
+                format!("{:08}", rng.gen_range(0, length))
+            } else {
+                format!("{:08}", i)
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_try_reserve_sort() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    for &disorder_factor in &[0.0, 0.01, 0.1, 0.5, 1.0] {
+        for &max_dropped_bytes in &[0, 64, 1024, usize::MAX] {
+            let input = generate_strings(&mut rng, 1000, disorder_factor);
+            let mut expected = input.clone();
+            expected.sort();
+
+            let mut data = input.clone();
+            let result = with_max_alloc_size(max_dropped_bytes, || {
+                dmsort::try_reserve_sort(&mut data, AllocPolicy::Fallback)
+            });
+            assert!(result.is_ok());
+            assert_eq!(data, expected);
+
+            let mut data = input.clone();
+            let result = with_max_alloc_size(max_dropped_bytes, || {
+                dmsort::try_reserve_sort(&mut data, AllocPolicy::Error)
+            });
+            match result {
+                Ok(()) => assert_eq!(data, expected),
+                Err(_) => {
+                    assert!(max_dropped_bytes < usize::MAX);
+                    // All elements must still be there:
+                    data.sort();
+                    assert_eq!(data, expected);
+                }
+            }
+        }
+    }
+
+    // Make sure the error path is taken at all:
+    let mut data = generate_strings(&mut rng, 1000, 0.1);
+    let result = with_max_alloc_size(64, || {
+        dmsort::try_reserve_sort(&mut data, AllocPolicy::Error)
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_max_dropped_memory() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    for &disorder_factor in &[0.01, 0.1, 0.5] {
+        let mut data = generate_strings(&mut rng, 10_000, disorder_factor);
+        let mut expected = data.clone();
+        expected.sort();
+
+        // More than fit in the 512 bytes the dropped elements are kept in on the stack:
+        let max_dropped = 64;
+        let max_dropped_bytes = max_dropped * std::mem::size_of::<String>();
+        let config = dmsort::DmSortConfig {
+            max_dropped,
+            ..Default::default()
+        };
+        // Any bigger allocation aborts the process:
+        let stats = with_max_alloc_size(max_dropped_bytes, || {
+            dmsort::sort_by_with_config(&mut data, &config, |a, b| a.cmp(b))
+        });
+        assert_eq!(data, expected);
+
+        // The dropped elements spilled over to the heap, and then hit the cap:
+        assert!(largest_alloc_size() > 512);
+        assert!(largest_alloc_size() <= max_dropped_bytes);
+        assert!(stats.num_flushes > 0);
+    }
+}
+
+#[test]
+fn test_sort_in_place_allocates_nothing() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    for &disorder_factor in &[0.0, 0.01, 0.1, 0.5, 1.0] {
+        let mut data = generate_strings(&mut rng, 1000, disorder_factor);
+        let mut expected = data.clone();
+        expected.sort();
+
+        // Any allocation aborts the process:
+        with_max_alloc_size(0, || dmsort::sort_in_place(&mut data));
+        assert_eq!(data, expected);
+    }
+}
+
+#[test]
+fn test_few_outliers_allocate_nothing() {
+    let mut data: Vec<i32> = (0..10_000).collect();
+    data[10] = 9_000;
+    data[5_000] = -1;
+    data[9_000] = 42;
+    let mut expected = data.clone();
+    expected.sort();
+
+    // Any allocation aborts the process:
+    with_max_alloc_size(0, || dmsort::sort(&mut data));
+    assert_eq!(data, expected);
+}
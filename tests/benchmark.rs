@@ -53,7 +53,7 @@ where
         let start_time_ns = time::precise_time_ns();
         sorter(&mut vec_clone);
         let duration_ns = time::precise_time_ns() - start_time_ns;
-        if best_ns == None || duration_ns < best_ns.unwrap() {
+        if best_ns.is_none() || duration_ns < best_ns.unwrap() {
             best_ns = Some(duration_ns);
         }
         sorted = vec_clone;
@@ -77,6 +77,10 @@ fn get_bench_disorders() -> Vec<f32> {
         .collect()
 }
 
+/// Another sort function to plot next to `dmsort::sort`, and its name.
+type ExtraSorter<'a, T> = (&'a str, fn(&mut [T]));
+
+#[allow(clippy::too_many_arguments)]
 fn benchmark_and_plot<T, G>(
     rng: &mut StdRng,
     num_best_of: usize,
@@ -85,6 +89,7 @@ fn benchmark_and_plot<T, G>(
     element_type_short: &str,
     element_type_long: &str,
     mut generator: G,
+    extra_sorter: Option<ExtraSorter<T>>,
 ) where
     T: std::fmt::Debug + Clone + std::cmp::Ord,
     G: FnMut(&mut StdRng, usize, f32) -> Vec<T>,
@@ -100,12 +105,18 @@ fn benchmark_and_plot<T, G>(
     let mut pdq_ms_list = vec![];
     let mut dmsort_ms_list = vec![];
     let mut dmsort_speedup_list = vec![];
+    let mut extra_ms_list = vec![];
 
     for &disorder_factor in &bench_disorders {
         let vec = generator(rng, length, disorder_factor);
         let (std_ms, std_sorted) = time_sort_ms(num_best_of, &vec, |x| x.sort());
         let (pdq_ms, pdq_sorted) = time_sort_ms(num_best_of, &vec, |x| x.sort_unstable());
         let (dmsort_ms, dmsort_sorted) = time_sort_ms(num_best_of, &vec, |x| dmsort::sort(x));
+        if let Some((_, extra_sorter)) = extra_sorter {
+            let (extra_ms, extra_sorted) = time_sort_ms(num_best_of, &vec, |x| extra_sorter(x));
+            assert_eq!(extra_sorted, std_sorted);
+            extra_ms_list.push(extra_ms);
+        }
 
         let fastest_competitor_ms = std_ms.min(pdq_ms);
 
@@ -133,7 +144,7 @@ fn benchmark_and_plot<T, G>(
                 length_str, element_type_short
             ),
         );
-        figure
+        let axes = figure
             .axes2d()
             .set_legend(
                 Graph(1.0),
@@ -171,6 +182,13 @@ fn benchmark_and_plot<T, G>(
                 &dmsort_ms_list,
                 &[Caption("Drop-Merge Sort"), Color("#4444FF"), LineWidth(2.0)],
             );
+        if let Some((extra_name, _)) = extra_sorter {
+            axes.lines(
+                &disorder_percentages,
+                &extra_ms_list,
+                &[Caption(extra_name), Color("#00AA00"), LineWidth(2.0)],
+            );
+        }
         figure.show();
     }
     {
@@ -207,7 +225,7 @@ fn benchmark_and_plot<T, G>(
             .set_y_label("Speedup over fastest competitor", &[])
             .set_x_range(Fix(0.0), Fix(50.0))
             .set_y_range(Fix(0.0), Fix(8.0))
-            .lines(&[0.0, 100.0], &[1.0, 1.0], &[Color("#606060")])
+            .lines([0.0, 100.0], [1.0, 1.0], &[Color("#606060")])
             .lines(
                 &disorder_percentages,
                 &dmsort_speedup_list,
@@ -234,6 +252,28 @@ fn bench_evil() {
     println!("Drop-Merge sort: {} ms", drop_ms);
 }
 
+/// Benchmark `adaptive::sort`, which should never be noticeably slower than `sort_unstable`
+fn bench_adaptive(rng: &mut StdRng) {
+    let disorders = [0.0, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0];
+    println!("adaptive::sort time / sort_unstable time:");
+    println!("{:>9} {:?}", "length", disorders);
+    for &length in &[10, 100, 1_000, 10_000, 100_000, 1_000_000] {
+        let num_best_of = (10_000_000 / length).clamp(5, 1000);
+        let ratios: Vec<String> = disorders
+            .iter()
+            .map(|&disorder_factor| {
+                let vec = generate_integers(rng, length, disorder_factor);
+                let (pdq_ms, pdq_sorted) = time_sort_ms(num_best_of, &vec, |x| x.sort_unstable());
+                let (adaptive_ms, adaptive_sorted) =
+                    time_sort_ms(num_best_of, &vec, |x| dmsort::adaptive::sort(x));
+                assert_eq!(adaptive_sorted, pdq_sorted);
+                format!("{:.2}", adaptive_ms / pdq_ms)
+            })
+            .collect();
+        println!("{:>9} {}", length, ratios.join(" "));
+    }
+}
+
 #[test]
 #[ignore]
 #[rustfmt::skip]
@@ -244,15 +284,17 @@ fn benchmarks() {
     let seed = [0; 32];
     let mut rng: StdRng = StdRng::from_seed(seed);
 
-    benchmark_and_plot(&mut rng, 1000,        100, "100",  "i32",    "32-bit integers",  generate_integers);
-    benchmark_and_plot(&mut rng, 1000,        100, "100",  "string", "100-byte strings", generate_strings);
-    benchmark_and_plot(&mut rng, 1000,      1_000, "1000", "i32",    "32-bit integers",  generate_integers);
-    benchmark_and_plot(&mut rng,  100,      1_000, "1000", "string", "100-byte strings", generate_strings);
-    benchmark_and_plot(&mut rng,  100,     10_000, "10k",  "i32",    "32-bit integers",  generate_integers);
-    benchmark_and_plot(&mut rng,   10,     10_000, "10k",  "string", "100-byte strings", generate_strings);
-    benchmark_and_plot(&mut rng,   10,    100_000, "100k", "i32",    "32-bit integers",  generate_integers);
-    benchmark_and_plot(&mut rng,    3,    100_000, "100k", "string", "100-byte strings", generate_strings);
-    benchmark_and_plot(&mut rng,    5,  1_000_000, "1M",   "i32",    "32-bit integers",  generate_integers);
-    benchmark_and_plot(&mut rng,    1,  1_000_000, "1M",   "string", "100-byte strings", generate_strings);
-    benchmark_and_plot(&mut rng,    1, 10_000_000, "10M",  "i32",    "32-bit integers",  generate_integers);
+    bench_adaptive(&mut rng);
+
+    benchmark_and_plot(&mut rng, 1000,        100, "100",  "i32",    "32-bit integers",  generate_integers, None);
+    benchmark_and_plot(&mut rng, 1000,        100, "100",  "string", "100-byte strings", generate_strings,  Some(("dmsort::sort_strings", dmsort::sort_strings)));
+    benchmark_and_plot(&mut rng, 1000,      1_000, "1000", "i32",    "32-bit integers",  generate_integers, None);
+    benchmark_and_plot(&mut rng,  100,      1_000, "1000", "string", "100-byte strings", generate_strings,  Some(("dmsort::sort_strings", dmsort::sort_strings)));
+    benchmark_and_plot(&mut rng,  100,     10_000, "10k",  "i32",    "32-bit integers",  generate_integers, None);
+    benchmark_and_plot(&mut rng,   10,     10_000, "10k",  "string", "100-byte strings", generate_strings,  Some(("dmsort::sort_strings", dmsort::sort_strings)));
+    benchmark_and_plot(&mut rng,   10,    100_000, "100k", "i32",    "32-bit integers",  generate_integers, None);
+    benchmark_and_plot(&mut rng,    3,    100_000, "100k", "string", "100-byte strings", generate_strings,  Some(("dmsort::sort_strings", dmsort::sort_strings)));
+    benchmark_and_plot(&mut rng,    5,  1_000_000, "1M",   "i32",    "32-bit integers",  generate_integers, None);
+    benchmark_and_plot(&mut rng,    1,  1_000_000, "1M",   "string", "100-byte strings", generate_strings,  Some(("dmsort::sort_strings", dmsort::sort_strings)));
+    benchmark_and_plot(&mut rng,    1, 10_000_000, "10M",  "i32",    "32-bit integers",  generate_integers, None);
 }
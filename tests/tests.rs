@@ -1,9 +1,61 @@
 extern crate dmsort;
+extern crate rand;
 
 use std::cell::RefCell;
 use std::collections::BTreeSet;
 use std::panic;
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Returns a mostly-sorted array with `disorder_factor` fraction of elements with random values.
+fn generate_integers(rng: &mut StdRng, length: usize, disorder_factor: f32) -> Vec<i32> {
+    (0..length)
+        .map(|i| {
+            if rng.gen::<f32>() < disorder_factor {
+                rng.gen_range(0, length as i32)
+            } else {
+                i as i32
+            }
+        })
+        .collect()
+}
+
+/// Calls `test` with the output of `generate_integers` for each length and disorder factor,
+/// starting from the same seed every time.
+fn for_each_disorder<F>(lengths: &[usize], disorder_factors: &[f32], mut test: F)
+where
+    F: FnMut(Vec<i32>),
+{
+    let mut rng = StdRng::from_seed([0; 32]);
+    for &length in lengths {
+        for &disorder_factor in disorder_factors {
+            test(generate_integers(&mut rng, length, disorder_factor));
+        }
+    }
+}
+
+/// Records when it is dropped, so that we can check that every element is dropped exactly once,
+/// also when the sort is interrupted by a panic or an error.
+struct TestSortType<'a> {
+    id: usize,
+    dropped: &'a RefCell<BTreeSet<usize>>,
+}
+
+impl<'a> Drop for TestSortType<'a> {
+    fn drop(&mut self) {
+        let did_insert = self.dropped.borrow_mut().insert(self.id);
+        assert!(did_insert, "Double-free of {}", self.id);
+    }
+}
+
+/// One `TestSortType` for each of `ids`.
+fn test_sort_types<'a>(
+    ids: &[usize],
+    dropped: &'a RefCell<BTreeSet<usize>>,
+) -> Vec<TestSortType<'a>> {
+    ids.iter().map(|&id| TestSortType { id, dropped }).collect()
+}
+
 #[test]
 fn simple_tests() {
     fn test_type<T: Clone + PartialEq + Ord + std::fmt::Debug>(unsorted: Vec<T>) {
@@ -50,53 +102,13 @@ fn test_unwind() {
     // contains all elements it had when called (but maybe in a different, partially-sorted order).
     // This is crucial in order to prevent double-frees.
     //
-    struct TestSortType<'a> {
-        id: usize,
-        dropped: &'a RefCell<BTreeSet<usize>>,
-    }
-    impl<'a> Drop for TestSortType<'a> {
-        fn drop(&mut self) {
-            let did_insert = self.dropped.borrow_mut().insert(self.id);
-            assert!(did_insert, "Double-free of {}", self.id);
-        }
-    }
-
     for break_after_this_many_comparisons in 0..14 {
         let scheuled_panic_code: String = String::from("This is a scheduled panic");
 
         let dropped = RefCell::new(BTreeSet::new());
 
         let catch_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
-            let mut data = [
-                TestSortType {
-                    id: 0,
-                    dropped: &dropped,
-                },
-                TestSortType {
-                    id: 1,
-                    dropped: &dropped,
-                },
-                TestSortType {
-                    id: 5,
-                    dropped: &dropped,
-                },
-                TestSortType {
-                    id: 6,
-                    dropped: &dropped,
-                },
-                TestSortType {
-                    id: 2,
-                    dropped: &dropped,
-                },
-                TestSortType {
-                    id: 3,
-                    dropped: &dropped,
-                },
-                TestSortType {
-                    id: 4,
-                    dropped: &dropped,
-                },
-            ];
+            let mut data = test_sort_types(&[0, 1, 5, 6, 2, 3, 4], &dropped);
             let mut num_comparisons = 0;
 
             dmsort::sort_by(&mut data, |a, b| {
@@ -127,3 +139,1133 @@ fn test_unwind() {
         assert_eq!(dropped.borrow_mut().len(), 7);
     }
 }
+
+#[test]
+fn test_select_nth() {
+    fn test(unsorted: Vec<i32>) {
+        let mut sorted = unsorted.clone();
+        sorted.sort();
+
+        for n in 0..unsorted.len() {
+            let mut selected = unsorted.clone();
+            let nth = *dmsort::select_nth(&mut selected, n);
+            assert_eq!(nth, sorted[n], "FAIL with n={} input {:?}", n, unsorted);
+            assert!(selected[..n].iter().all(|&x| x <= nth));
+            assert!(selected[n..].iter().all(|&x| x >= nth));
+
+            selected.sort();
+            assert_eq!(selected, sorted, "Lost elements with input {:?}", unsorted);
+        }
+    }
+
+    test(vec![0]);
+    test(vec![1, 0]);
+    test(vec![2, 1, 0]);
+    test(vec![0, 1, 3, 2, 4, -5, 6, 7, 8, 9]);
+    test(vec![0, 0, 2, 3, 4, 1, 6, 1, 8, 9]);
+    test(vec![
+        20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    ]);
+
+    for_each_disorder(&[200], &[0.0, 0.05, 0.2, 0.5, 1.0], test);
+}
+
+#[test]
+fn test_sort_dedup() {
+    fn test(unsorted: Vec<i32>) {
+        let mut expected = unsorted.clone();
+        expected.sort();
+        expected.dedup();
+
+        let mut deduped = unsorted.clone();
+        dmsort::sort_dedup(&mut deduped);
+        assert_eq!(deduped, expected, "FAIL with input {:?}", unsorted);
+
+        // Keep the last of each duplicate in the input:
+        let mut pairs: Vec<(i32, usize)> = unsorted.iter().cloned().zip(0..).collect();
+        dmsort::sort_dedup_by(
+            &mut pairs,
+            |a, b| a.0.cmp(&b.0),
+            |removed, survivor| {
+                if removed.1 > survivor.1 {
+                    std::mem::swap(removed, survivor);
+                }
+            },
+        );
+        assert_eq!(pairs.len(), expected.len());
+        for &(key, index) in &pairs {
+            let last_index = unsorted.iter().rposition(|&x| x == key).unwrap();
+            assert_eq!(index, last_index, "FAIL with input {:?}", unsorted);
+        }
+    }
+
+    test(vec![]);
+    test(vec![0]);
+    test(vec![0, 0]);
+    test(vec![1, 0, 1]);
+    test(vec![0, 1, 1, 0, 2, 2]);
+    test(vec![0, 0, 2, 3, 4, 1, 6, 1, 8, 9]);
+    test(vec![5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5]);
+    test(vec![
+        20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20,
+    ]);
+
+    for_each_disorder(&[200; 20], &[0.0, 0.05, 0.2, 0.5, 1.0], |integers| {
+        test(integers.iter().map(|x| x / 4).collect())
+    });
+}
+
+#[test]
+fn test_sort_dedup_unwind() {
+    // Elements can also be removed as duplicates before the panic.
+    let keys = [0, 1, 5, 1, 6, 2, 3, 3, 4, 0, 7, 8];
+    let ids: Vec<usize> = (0..keys.len()).collect();
+
+    for break_after_this_many_comparisons in 0..40 {
+        let dropped = RefCell::new(BTreeSet::new());
+
+        let catch_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let mut data = test_sort_types(&ids, &dropped);
+            let mut num_comparisons = 0;
+
+            dmsort::sort_dedup_by(
+                &mut data,
+                |a, b| {
+                    if num_comparisons == break_after_this_many_comparisons {
+                        panic!("This is a scheduled panic");
+                    }
+                    num_comparisons += 1;
+                    keys[a.id].cmp(&keys[b.id])
+                },
+                |_, _| {},
+            );
+        }));
+
+        if catch_result.is_ok() {
+            break; // We finished before reaching the scheduled panic.
+        }
+
+        // Make sure we dropped all objects:
+        assert_eq!(dropped.borrow_mut().len(), keys.len());
+    }
+}
+
+#[test]
+fn test_argsort() {
+    for_each_disorder(&[1000], &[0.0, 0.05, 0.2, 0.5, 1.0], |integers| {
+        let strings: Vec<String> = integers.iter().map(|x| format!("{:04}", x)).collect();
+
+        let mut sorted = integers.clone();
+        sorted.sort();
+
+        let perm = dmsort::argsort(&integers);
+        let gathered: Vec<i32> = perm.iter().map(|&i| integers[i]).collect();
+        assert_eq!(gathered, sorted);
+
+        let perm_u32 = dmsort::argsort_u32(&integers);
+        let gathered: Vec<i32> = perm_u32.iter().map(|&i| integers[i as usize]).collect();
+        assert_eq!(gathered, sorted);
+
+        let mut columns = (integers.clone(), strings.clone());
+        dmsort::apply_permutation_in_place(&perm_u32, (&mut columns.0, &mut columns.1));
+        assert_eq!(columns.0, sorted);
+        let expected_strings: Vec<String> = perm.iter().map(|&i| strings[i].clone()).collect();
+        assert_eq!(columns.1, expected_strings);
+    });
+
+    // Nothing is moved if it is not a permutation:
+    for bad_perm in &[vec![1usize, 2, 0, 4, 4], vec![1, 2, 0, 5, 3]] {
+        let mut column = vec![0, 1, 2, 3, 4];
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            dmsort::apply_permutation_in_place(bad_perm, &mut column[..]);
+        }));
+        assert!(result.is_err());
+        assert_eq!(column, vec![0, 1, 2, 3, 4]);
+    }
+}
+
+#[test]
+fn test_sort_zip() {
+    fn test(mut keys: Vec<i32>) {
+        let mut strings: Vec<String> = keys.iter().map(|x| format!("{:04}", x)).collect();
+        let mut negated: Vec<i64> = keys.iter().map(|&x| -(x as i64)).collect();
+
+        let mut sorted = keys.clone();
+        sorted.sort();
+
+        dmsort::sort_zip(&mut keys, (&mut strings, &mut negated[..]));
+        assert_eq!(keys, sorted);
+        for i in 0..keys.len() {
+            assert_eq!(strings[i], format!("{:04}", keys[i]));
+            assert_eq!(negated[i], -(keys[i] as i64));
+        }
+    }
+
+    for_each_disorder(&[1000], &[0.0, 0.05, 0.2, 0.5, 1.0], test);
+
+    // Early-out after a sorted start, which is salvaged:
+    let mut rng = StdRng::from_seed([0; 32]);
+    let mut keys = generate_integers(&mut rng, 500, 0.0);
+    keys.extend(generate_integers(&mut rng, 500, 1.0));
+    test(keys);
+}
+
+#[test]
+fn test_sort_zip_unwind() {
+    // The companion column must be restored in lockstep with the keys.
+    let keys = [0, 1, 5, 6, 2, 3, 4, 12, 7, 8, 9, 10, 11];
+
+    for break_after_this_many_comparisons in 0..40 {
+        let dropped = RefCell::new(BTreeSet::new());
+        let mut sorted_keys = keys.to_vec();
+        let mut column = test_sort_types(&keys, &dropped);
+
+        let catch_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let mut num_comparisons = 0;
+            dmsort::sort_zip_by(&mut sorted_keys, &mut column, |a, b| {
+                if num_comparisons == break_after_this_many_comparisons {
+                    panic!("This is a scheduled panic");
+                }
+                num_comparisons += 1;
+                a.cmp(b)
+            });
+        }));
+
+        for (key, element) in sorted_keys.iter().zip(&column) {
+            assert_eq!(*key, element.id);
+        }
+        drop(column);
+        assert_eq!(dropped.borrow_mut().len(), keys.len());
+
+        if catch_result.is_ok() {
+            break; // We finished before reaching the scheduled panic.
+        }
+    }
+}
+
+#[test]
+fn test_floats() {
+    use dmsort::NanPolicy;
+
+    for_each_disorder(&[1000], &[0.0, 0.05, 0.2, 0.5, 1.0], |integers| {
+        let mut floats: Vec<f64> = integers
+            .iter()
+            .map(|&x| (x as f64 - 500.0) / 10.0)
+            .collect();
+        floats[17] = f64::NAN;
+        floats[42] = -f64::NAN;
+        floats[100] = -0.0;
+
+        let mut expected = floats.clone();
+        expected.sort_by(|a, b| a.total_cmp(b));
+        let mut sorted = floats.clone();
+        dmsort::sort_floats(&mut sorted);
+        let to_bits = |v: &[f64]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+        assert_eq!(to_bits(&sorted), to_bits(&expected));
+
+        let mut nans_first = floats.clone();
+        dmsort::sort_by_partial_key(&mut nans_first, |&x| x, NanPolicy::First).unwrap();
+        assert!(nans_first[..2].iter().all(|x| x.is_nan()));
+        assert!(nans_first[2..].windows(2).all(|w| w[0] <= w[1]));
+
+        let mut nans_last = floats.clone();
+        dmsort::sort_by_partial_key(&mut nans_last, |&x| x, NanPolicy::Last).unwrap();
+        assert!(nans_last[998..].iter().all(|x| x.is_nan()));
+        assert!(nans_last[..998].windows(2).all(|w| w[0] <= w[1]));
+
+        let mut unchanged = floats.clone();
+        assert_eq!(
+            dmsort::sort_by_partial_key(&mut unchanged, |&x| x, NanPolicy::Error),
+            Err(dmsort::NanError)
+        );
+        assert_eq!(to_bits(&unchanged), to_bits(&floats));
+
+        let mut no_nans: Vec<f64> = floats.iter().cloned().filter(|x| !x.is_nan()).collect();
+        dmsort::sort_by_partial_key(&mut no_nans, |&x| x, NanPolicy::Error).unwrap();
+        assert!(no_nans.windows(2).all(|w| w[0] <= w[1]));
+    });
+}
+
+#[test]
+fn test_sub_sorters() {
+    use std::cmp::Ordering;
+
+    /// Counts how many elements it was asked to sort.
+    #[derive(Default)]
+    struct CountingSorter {
+        num_sorted: usize,
+    }
+    impl<T> dmsort::SubSorter<T> for CountingSorter {
+        fn sort_by<F>(&mut self, slice: &mut [T], compare: F)
+        where
+            F: FnMut(&T, &T) -> Ordering,
+        {
+            self.num_sorted += slice.len();
+            dmsort::InsertionSort.sort_by(slice, compare);
+        }
+    }
+
+    let mut rng = StdRng::from_seed([0; 32]);
+
+    let mut dropped_sorter = CountingSorter::default();
+    let mut fallback_sorter = CountingSorter::default();
+    let mut nearly_sorted = generate_integers(&mut rng, 1000, 0.05);
+    let mut expected = nearly_sorted.clone();
+    expected.sort();
+    dmsort::sort_by_with_sorters(
+        &mut nearly_sorted,
+        &mut dropped_sorter,
+        &mut fallback_sorter,
+        |a, b| a.cmp(b),
+    );
+    assert_eq!(nearly_sorted, expected);
+    assert!(0 < dropped_sorter.num_sorted && dropped_sorter.num_sorted < 200);
+    assert_eq!(fallback_sorter.num_sorted, 0);
+
+    let mut dropped_sorter = CountingSorter::default();
+    let mut fallback_sorter = CountingSorter::default();
+    let mut random = generate_integers(&mut rng, 1000, 1.0);
+    let mut expected = random.clone();
+    expected.sort();
+    dmsort::sort_by_with_sorters(
+        &mut random,
+        &mut dropped_sorter,
+        &mut fallback_sorter,
+        |a, b| a.cmp(b),
+    );
+    assert_eq!(random, expected);
+    assert_eq!(dropped_sorter.num_sorted, 0);
+    assert_eq!(fallback_sorter.num_sorted, 1000);
+
+    for &disorder_factor in &[0.0, 0.05, 0.2, 0.5, 1.0] {
+        let unsorted = generate_integers(&mut rng, 500, disorder_factor);
+        let mut expected = unsorted.clone();
+        expected.sort();
+
+        let mut sorted = unsorted.clone();
+        dmsort::sort_by_with_sorters(
+            &mut sorted,
+            &mut dmsort::SortStable,
+            &mut dmsort::InsertionSort,
+            |a, b| a.cmp(b),
+        );
+        assert_eq!(sorted, expected);
+    }
+}
+
+#[test]
+fn test_sort_radix() {
+    fn test<T: dmsort::RadixKey + Clone + PartialEq + std::fmt::Debug>(unsorted: Vec<T>) {
+        let mut expected = unsorted.clone();
+        expected.sort_by(|a, b| a.radix_cmp(b));
+        let mut sorted = unsorted.clone();
+        dmsort::sort_radix(&mut sorted);
+        assert_eq!(sorted, expected);
+    }
+
+    for_each_disorder(&[2000], &[0.0, 0.05, 0.2, 0.5, 1.0], |integers| {
+        test(integers.clone());
+        test(integers.iter().map(|&x| x - 1000).collect::<Vec<i32>>());
+        test(
+            integers
+                .iter()
+                .map(|&x| x as u64 * 1_000_003)
+                .collect::<Vec<u64>>(),
+        );
+        test(
+            integers
+                .iter()
+                .map(|&x| (x as i8, x as u16))
+                .collect::<Vec<_>>(),
+        );
+        test(
+            integers
+                .iter()
+                .map(|&x| (x as f32 - 1000.0) / 7.0)
+                .collect::<Vec<f32>>(),
+        );
+        test(integers.iter().map(|&x| -(x as f64)).collect::<Vec<f64>>());
+
+        let strings: Vec<String> = integers.iter().map(|x| format!("{}", x % 300)).collect();
+        test(strings.clone());
+        test(
+            strings
+                .iter()
+                .map(|s| (s.clone(), s.len() as u8))
+                .collect::<Vec<_>>(),
+        );
+        test(
+            strings
+                .iter()
+                .map(|s| s.as_bytes().to_vec())
+                .collect::<Vec<_>>(),
+        );
+
+        let mut sorted = strings.clone();
+        dmsort::sort_by_radix_key(&mut sorted, |s| (s.len() as u32, s.clone()));
+        let mut expected = strings.clone();
+        expected.sort_by_key(|s| (s.len(), s.clone()));
+        assert_eq!(sorted, expected);
+    });
+}
+
+#[test]
+fn test_merge_comparisons() {
+    // A few small elements at the end must be merged past the whole kept run.
+    // Galloping should find their place in O(log(N)) comparisons each.
+    let len = 100_000;
+    let mut numbers: Vec<i32> = (5..len).chain(0..5).collect();
+    let mut num_comparisons = 0;
+    dmsort::sort_by(&mut numbers, |a, b| {
+        num_comparisons += 1;
+        a.cmp(b)
+    });
+    assert_eq!(numbers, (0..len).collect::<Vec<_>>());
+    assert!(
+        num_comparisons < len as usize + 200,
+        "Too many comparisons: {}",
+        num_comparisons
+    );
+}
+
+#[test]
+fn test_backtracking_comparisons() {
+    // The small elements at the end make us backtrack over the whole kept run.
+    // Walking back one comparison at a time took about 4 * len comparisons in total,
+    // galloping back takes about 3 * len.
+    let len = 100_000;
+    let mut numbers: Vec<i32> = (100..len).chain(0..100).collect();
+    let mut num_comparisons = 0;
+    dmsort::sort_by(&mut numbers, |a, b| {
+        num_comparisons += 1;
+        a.cmp(b)
+    });
+    assert_eq!(numbers, (0..len).collect::<Vec<_>>());
+    assert!(
+        num_comparisons < 7 * len as usize / 2,
+        "Too many comparisons: {}",
+        num_comparisons
+    );
+}
+
+#[test]
+fn test_adversarial_comparisons() {
+    fn count_comparisons(unsorted: &[i64]) -> usize {
+        let mut sorted = unsorted.to_vec();
+        let mut num_comparisons = 0;
+        dmsort::sort_by(&mut sorted, |a, b| {
+            num_comparisons += 1;
+            a.cmp(b)
+        });
+        let mut expected = unsorted.to_vec();
+        expected.sort();
+        assert_eq!(sorted, expected);
+        num_comparisons
+    }
+
+    let len = 100_000;
+    let max_comparisons = 4 * len * 17; // 17 = log2(len), rounded up
+
+    for &sorted_prefix in &[0, len / 3] {
+        // An ordered start fools the early-out test, then we force as much backtracking as we can:
+        for &block in &[2, 9, 10, 30] {
+            let mut input: Vec<i64> = (0..sorted_prefix as i64).map(|x| x - len as i64).collect();
+            let mut x = 0;
+            while input.len() < len {
+                input.push(x + 2 * block);
+                input.extend((0..block).map(|i| x + i));
+                x += 2 * block + 1;
+            }
+            assert!(count_comparisons(&input) < max_comparisons);
+        }
+
+        for &period in &[9, 10, 100] {
+            let mut input: Vec<i64> = (0..sorted_prefix as i64).map(|x| x - len as i64).collect();
+            input.extend(
+                (0..(len - sorted_prefix) as i64).map(|i| (i % period) * 1000 + i / period),
+            );
+            assert!(count_comparisons(&input) < max_comparisons);
+        }
+    }
+}
+
+#[test]
+fn test_early_out_checkpoints() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let len = 10_000;
+    let config = dmsort::DmSortConfig::default();
+
+    let sort = |input: &[i32], config: &dmsort::DmSortConfig| {
+        let mut sorted = input.to_vec();
+        let stats = dmsort::sort_by_with_config(&mut sorted, config, |a, b| a.cmp(b));
+        let mut expected = input.to_vec();
+        expected.sort();
+        assert_eq!(sorted, expected);
+        assert_eq!(stats.len, len);
+        stats
+    };
+
+    let nearly_sorted = generate_integers(&mut rng, len, 0.05);
+    let stats = sort(&nearly_sorted, &config);
+    assert_eq!(stats.early_out, None);
+    assert!(0 < stats.num_dropped && stats.num_dropped < len / 10);
+
+    let random = generate_integers(&mut rng, len, 1.0);
+    match sort(&random, &config).early_out {
+        Some(dmsort::EarlyOut::Disorder {
+            checkpoint,
+            num_read,
+        }) => {
+            assert_eq!(checkpoint, 0);
+            assert!(num_read <= len / 4);
+        }
+        early_out => panic!("Unexpected early-out: {:?}", early_out),
+    }
+
+    // Sorted at the start and random afterwards, which the first check does not catch:
+    let mut sorted_then_random = generate_integers(&mut rng, len / 3, 0.0);
+    sorted_then_random.extend(generate_integers(&mut rng, len - len / 3, 1.0));
+    match sort(&sorted_then_random, &config).early_out {
+        Some(dmsort::EarlyOut::Disorder {
+            checkpoint,
+            num_read,
+        }) => {
+            assert!(0 < checkpoint);
+            assert!(len / 3 < num_read);
+        }
+        early_out => panic!("Unexpected early-out: {:?}", early_out),
+    }
+
+    // The sorted start is kept and merged with the rest instead of being sorted again:
+    let stats = sort(&sorted_then_random, &config);
+    assert!(len / 4 <= stats.num_salvaged);
+    assert_eq!(sort(&random, &config).num_salvaged, 0);
+
+    let never_give_up = dmsort::DmSortConfig {
+        early_out: false,
+        ..config
+    };
+    assert_eq!(sort(&random, &never_give_up).early_out, None);
+    assert_eq!(sort(&sorted_then_random, &never_give_up).early_out, None);
+
+    let give_up_easily = dmsort::DmSortConfig {
+        early_out_disorder_fraction: 0.01,
+        ..config
+    };
+    assert!(sort(&nearly_sorted, &give_up_easily).early_out.is_some());
+}
+
+#[test]
+fn test_precheck() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let config = dmsort::DmSortConfig {
+        precheck: true,
+        ..Default::default()
+    };
+
+    let mut check = |len: usize, disorder_factor: f32, algorithm: dmsort::Algorithm| {
+        let unsorted = generate_integers(&mut rng, len, disorder_factor);
+        let mut sorted = unsorted.clone();
+        let stats = dmsort::sort_by_with_config(&mut sorted, &config, |a, b| a.cmp(b));
+        let mut expected = unsorted.clone();
+        expected.sort();
+        assert_eq!(sorted, expected);
+
+        let precheck = stats.precheck.unwrap();
+        assert_eq!(precheck.algorithm, algorithm, "{:?}", precheck);
+        assert!(precheck.num_probes < 600);
+        if algorithm == dmsort::Algorithm::DropMerge {
+            let error = precheck.estimated_disorder() - disorder_factor;
+            assert!(error.abs() < 0.15, "{:?}", precheck);
+        }
+    };
+
+    check(10, 0.5, dmsort::Algorithm::InsertionSort);
+    check(30, 1.0, dmsort::Algorithm::InsertionSort);
+    check(100_000, 0.0, dmsort::Algorithm::DropMerge);
+    check(100_000, 0.1, dmsort::Algorithm::DropMerge);
+    check(100_000, 0.2, dmsort::Algorithm::DropMerge);
+    check(100_000, 0.7, dmsort::Algorithm::Fallback);
+    check(100_000, 1.0, dmsort::Algorithm::Fallback);
+
+    let mut numbers: Vec<i32> = vec![3, 2, 1];
+    let stats = dmsort::sort_by_with_config(&mut numbers, &Default::default(), |a, b| a.cmp(b));
+    assert_eq!(numbers, vec![1, 2, 3]);
+    assert_eq!(stats.precheck, None);
+}
+
+#[test]
+fn test_adaptive() {
+    let lengths = [0, 1, 2, 5, 8, 9, 50, 255, 256, 1000, 20_000];
+    for_each_disorder(&lengths, &[0.0, 0.01, 0.1, 0.5, 1.0], |integers| {
+        let mut expected = integers.clone();
+        expected.sort();
+
+        let mut sorted = integers.clone();
+        dmsort::adaptive::sort(&mut sorted);
+        assert_eq!(sorted, expected);
+
+        let mut sorted: Vec<String> = integers.iter().map(|x| format!("{:05}", x)).collect();
+        dmsort::adaptive::sort_by_key(&mut sorted, |s| s.parse::<i32>().unwrap());
+        let sorted: Vec<i32> = sorted.iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(sorted, expected);
+    });
+
+    // The sorted prefix is only compared once, also when the disorder is at the very end:
+    let len = 100_000;
+    let mut numbers: Vec<i64> = (0..len).collect();
+    numbers[len as usize - 10] = -1;
+    let mut num_comparisons = 0;
+    dmsort::adaptive::sort_by(&mut numbers, |a, b| {
+        num_comparisons += 1;
+        a.cmp(b)
+    });
+    assert!(numbers.windows(2).all(|w| w[0] <= w[1]));
+    assert!(
+        num_comparisons < len as usize + 1000,
+        "Too many comparisons: {}",
+        num_comparisons
+    );
+}
+
+#[test]
+fn test_insertion_sort_unwind() {
+    // A panic in the binary insertion sort that the pre-check picks for short slices.
+    let config = dmsort::DmSortConfig {
+        precheck: true,
+        ..Default::default()
+    };
+    let ids = [5, 1, 0, 6, 2, 7, 3, 4];
+
+    for break_after_this_many_comparisons in 0..30 {
+        let dropped = RefCell::new(BTreeSet::new());
+
+        let catch_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let mut data = test_sort_types(&ids, &dropped);
+            let mut num_comparisons = 0;
+
+            dmsort::sort_by_with_config(&mut data, &config, |a, b| {
+                if num_comparisons == break_after_this_many_comparisons {
+                    panic!("This is a scheduled panic");
+                }
+                num_comparisons += 1;
+                a.id.cmp(&b.id)
+            });
+        }));
+
+        if catch_result.is_ok() {
+            break; // We finished before reaching the scheduled panic.
+        }
+
+        // Make sure we dropped all objects:
+        assert_eq!(dropped.borrow_mut().len(), ids.len());
+    }
+}
+
+#[test]
+fn test_autotune() {
+    let mut rng = StdRng::from_seed([0; 32]);
+    let samples: Vec<Vec<i32>> = (0..4)
+        .map(|_| generate_integers(&mut rng, 1000, 0.1))
+        .collect();
+    let config = dmsort::autotune(&samples);
+    assert!(config.recency >= 1);
+
+    let mut data = generate_integers(&mut rng, 1000, 0.1);
+    let mut expected = data.clone();
+    expected.sort();
+    dmsort::sort_by_with_config(&mut data, &config, |a, b| a.cmp(b));
+    assert_eq!(data, expected);
+
+    // Timing is noisy, but comparisons are not. The tuned config is never worse than the default:
+    let count_comparisons = |config: &dmsort::DmSortConfig| {
+        let mut num_comparisons = 0;
+        for sample in &samples {
+            let mut sample = sample.clone();
+            dmsort::sort_by_with_config(&mut sample, config, |a, b| {
+                num_comparisons += 1;
+                a.cmp(b)
+            });
+        }
+        num_comparisons
+    };
+    let tuned = dmsort::autotune_by_comparisons(&samples, |a, b| a.cmp(b));
+    assert_eq!(
+        tuned,
+        dmsort::autotune_by_comparisons(&samples, |a, b| a.cmp(b))
+    );
+    assert!(count_comparisons(&tuned) <= count_comparisons(&Default::default()));
+
+    // Save and load:
+    let path =
+        std::env::temp_dir().join(format!("dmsort_test_autotune_{}.txt", std::process::id()));
+    config.save(&path).unwrap();
+    assert_eq!(dmsort::DmSortConfig::load(&path).unwrap(), config);
+    std::fs::remove_file(&path).unwrap();
+
+    // Missing settings get their default values, unknown settings are ignored:
+    let parsed: dmsort::DmSortConfig = "# Comment\nrecency = 3\nfrom_the_future = 42\n"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        parsed,
+        dmsort::DmSortConfig {
+            recency: 3,
+            ..Default::default()
+        }
+    );
+    assert!("recency = many".parse::<dmsort::DmSortConfig>().is_err());
+    assert!("recency".parse::<dmsort::DmSortConfig>().is_err());
+}
+
+#[test]
+fn test_try_sort() {
+    // The compare function returns an error instead of panicking.
+    // With full disorder the heapsort fallback is used:
+    for_each_disorder(&[100], &[0.2, 1.0], |integers| {
+        let ids: Vec<usize> = integers
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| x as usize * 1000 + i) // unique
+            .collect();
+
+        for fail_after_this_many_comparisons in 0.. {
+            let dropped = RefCell::new(BTreeSet::new());
+            let finished = {
+                let mut data = test_sort_types(&ids, &dropped);
+                let mut num_comparisons = 0;
+
+                let result = dmsort::try_sort_by(&mut data, |a, b| {
+                    if num_comparisons == fail_after_this_many_comparisons {
+                        return Err(num_comparisons);
+                    }
+                    num_comparisons += 1;
+                    Ok(a.id.cmp(&b.id))
+                });
+
+                let mut data_ids: Vec<usize> = data.iter().map(|x| x.id).collect();
+                match result {
+                    Ok(()) => {
+                        assert!(data_ids.windows(2).all(|w| w[0] <= w[1]));
+                    }
+                    Err(err) => {
+                        assert_eq!(err, fail_after_this_many_comparisons);
+                        assert_eq!(num_comparisons, fail_after_this_many_comparisons);
+                    }
+                }
+                data_ids.sort();
+                let mut expected = ids.clone();
+                expected.sort();
+                assert_eq!(data_ids, expected);
+                result.is_ok()
+            };
+
+            // Make sure we dropped all objects exactly once:
+            assert_eq!(dropped.borrow_mut().len(), ids.len());
+
+            if finished {
+                break;
+            }
+        }
+    });
+
+    let mut strings = vec!["3", "1", "2"];
+    assert_eq!(
+        dmsort::try_sort_by_key(&mut strings, |s| s.parse::<i32>()),
+        Ok(())
+    );
+    assert_eq!(strings, vec!["1", "2", "3"]);
+}
+
+#[test]
+fn test_max_dropped() {
+    for &policy in &[
+        dmsort::MaxDroppedPolicy::Merge,
+        dmsort::MaxDroppedPolicy::Fallback,
+    ] {
+        for &max_dropped in &[0, 1, 2, 7, 100, 10_000] {
+            let config = dmsort::DmSortConfig {
+                max_dropped,
+                max_dropped_policy: policy,
+                ..Default::default()
+            };
+            for_each_disorder(&[2000], &[0.0, 0.01, 0.1, 0.5, 1.0], |mut data| {
+                let mut expected = data.clone();
+                expected.sort();
+                let stats = dmsort::sort_by_with_config(&mut data, &config, |a, b| a.cmp(b));
+                assert_eq!(data, expected);
+
+                if policy == dmsort::MaxDroppedPolicy::Fallback {
+                    assert_eq!(stats.num_flushes, 0);
+                }
+                if stats.early_out.is_none() {
+                    assert!(stats.num_dropped <= (stats.num_flushes + 1) * max_dropped.max(1));
+                }
+            });
+        }
+    }
+
+    // Every 10th element is out of order:
+    let mut data: Vec<i32> = (0..1000)
+        .map(|i| if i % 10 == 5 { -i } else { i })
+        .collect();
+    let config = dmsort::DmSortConfig {
+        max_dropped: 10,
+        ..Default::default()
+    };
+    let stats = dmsort::sort_by_with_config(&mut data, &config, |a, b| a.cmp(b));
+    assert!(data.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(stats.early_out, None);
+    assert_eq!(stats.num_dropped, 100);
+    assert_eq!(stats.num_flushes, 9);
+
+    let mut data: Vec<i32> = (0..1000)
+        .map(|i| if i % 10 == 5 { -i } else { i })
+        .collect();
+    let config = dmsort::DmSortConfig {
+        max_dropped: 10,
+        max_dropped_policy: dmsort::MaxDroppedPolicy::Fallback,
+        ..Default::default()
+    };
+    let stats = dmsort::sort_by_with_config(&mut data, &config, |a, b| a.cmp(b));
+    assert!(data.windows(2).all(|w| w[0] <= w[1]));
+    assert_eq!(
+        stats.early_out,
+        Some(dmsort::EarlyOut::MaxDropped { num_read: 105 })
+    );
+}
+
+#[test]
+fn test_sort_in_place() {
+    let lengths = [0, 1, 2, 3, 10, 100, 1000, 10_000];
+    for_each_disorder(&lengths, &[0.0, 0.01, 0.1, 0.3, 0.5, 1.0], |mut data| {
+        let mut expected = data.clone();
+        expected.sort();
+        dmsort::sort_in_place(&mut data);
+        assert_eq!(data, expected);
+    });
+
+    let mut reversed: Vec<i32> = (0..1000).rev().collect();
+    dmsort::sort_in_place(&mut reversed);
+    assert_eq!(reversed, (0..1000).collect::<Vec<i32>>());
+
+    let mut evil: Vec<i32> = (100..10_000).chain(0..100).collect();
+    dmsort::sort_in_place(&mut evil);
+    assert_eq!(evil, (0..10_000).collect::<Vec<i32>>());
+
+    let mut strings: Vec<String> = vec!["b", "a", "d", "c"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    dmsort::sort_in_place_by_key(&mut strings, |s| s.clone());
+    assert_eq!(strings, vec!["a", "b", "c", "d"]);
+}
+
+#[test]
+fn test_unwind_spilled() {
+    // A panic with enough dropped elements that they no longer fit on the stack.
+    // Every other element is out of order:
+    let ids: Vec<usize> = (0..400)
+        .map(|i| if i % 2 == 0 { 1000 + i } else { i })
+        .collect();
+    // Boxed, so that we take the same merge path as `TestSortType`, which has drop glue:
+    let mut num_comparisons_needed = 0;
+    let mut boxed_ids: Vec<Box<usize>> = ids.iter().map(|&id| Box::new(id)).collect();
+    dmsort::sort_by(&mut boxed_ids, |a, b| {
+        num_comparisons_needed += 1;
+        a.cmp(b)
+    });
+
+    for break_after_this_many_comparisons in (0..num_comparisons_needed).step_by(7) {
+        let dropped = RefCell::new(BTreeSet::new());
+
+        let catch_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let mut data = test_sort_types(&ids, &dropped);
+            let mut num_comparisons = 0;
+
+            dmsort::sort_by(&mut data, |a, b| {
+                if num_comparisons == break_after_this_many_comparisons {
+                    panic!("This is a scheduled panic");
+                }
+                num_comparisons += 1;
+                a.id.cmp(&b.id)
+            });
+        }));
+
+        assert!(catch_result.is_err());
+
+        // Make sure we dropped all objects:
+        assert_eq!(dropped.borrow_mut().len(), ids.len());
+    }
+}
+
+#[test]
+fn test_sort_primitives() {
+    let mut inputs: Vec<Vec<i32>> = vec![
+        // Early-out with salvage, and runs that cross the checkpoints:
+        (0..1000).chain((0..1000).rev()).collect(),
+        (100..10_000).chain(0..100).collect(),
+        (0..5000).map(|i| i / 7).collect(),
+    ];
+    let lengths = [0, 1, 2, 3, 7, 8, 9, 15, 16, 17, 33, 100, 1000, 10_000];
+    for_each_disorder(&lengths, &[0.0, 0.001, 0.01, 0.1, 0.3, 0.5, 1.0], |data| {
+        inputs.push(data)
+    });
+
+    for data in inputs {
+        let mut expected = data.clone();
+        expected.sort();
+        let mut sorted = data.clone();
+        dmsort::sort_primitives(&mut sorted);
+        assert_eq!(sorted, expected);
+
+        // Shift into the high bit, so signed and unsigned order differ:
+        let unsigned: Vec<u32> = data
+            .iter()
+            .map(|&x| (x as u32).wrapping_add(0x7fff_fff0))
+            .collect();
+        let mut expected = unsigned.clone();
+        expected.sort();
+        let mut sorted = unsigned;
+        dmsort::sort_primitives(&mut sorted);
+        assert_eq!(sorted, expected);
+
+        let wide: Vec<u64> = data.iter().map(|&x| (x as u64) << 33 | 1).collect();
+        let mut expected = wide.clone();
+        expected.sort();
+        let mut sorted = wide;
+        dmsort::sort_primitives(&mut sorted);
+        assert_eq!(sorted, expected);
+
+        let floats: Vec<f32> = data.iter().map(|&x| x as f32 - 50.0).collect();
+        let mut expected = floats.clone();
+        dmsort::sort_floats(&mut expected);
+        let mut sorted = floats;
+        dmsort::sort_primitives(&mut sorted);
+        assert_eq!(sorted, expected);
+    }
+
+    let special = vec![
+        1.0,
+        f32::NAN,
+        -0.0,
+        0.0,
+        f32::NEG_INFINITY,
+        -f32::NAN,
+        f32::INFINITY,
+        -1.0,
+        0.0,
+        -0.0,
+    ];
+    let mut expected = special.clone();
+    dmsort::sort_floats(&mut expected);
+    let mut sorted = special;
+    dmsort::sort_primitives(&mut sorted);
+    let bits = |v: &[f32]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+    assert_eq!(bits(&sorted), bits(&expected));
+}
+
+#[test]
+fn test_branchless_merge() {
+    // Small plain-data elements with many out-of-order ones take the branchless merge.
+    let lengths = [2, 3, 10, 100, 1000, 10_000];
+    for_each_disorder(&lengths, &[0.1, 0.2, 0.3, 0.5], |integers| {
+        let data: Vec<(i32, usize)> = integers
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| (x / 4, i))
+            .collect();
+        let mut sorted = data.clone();
+        dmsort::sort_by_key(&mut sorted, |x| x.0);
+        assert!(sorted.windows(2).all(|w| w[0].0 <= w[1].0));
+        let mut ids: Vec<usize> = sorted.iter().map(|x| x.1).collect();
+        ids.sort();
+        assert!(ids.iter().cloned().eq(0..data.len()));
+    });
+
+    // A panic during the merge must leave all elements in the slice:
+    let ids: Vec<u64> = (0..400)
+        .map(|i| if i % 2 == 0 { 1000 + i } else { i })
+        .collect();
+    let mut num_comparisons_needed = 0;
+    dmsort::sort_by(&mut ids.clone(), |a, b| {
+        num_comparisons_needed += 1;
+        a.cmp(b)
+    });
+
+    for break_after_this_many_comparisons in (0..num_comparisons_needed).step_by(7) {
+        let mut data = ids.clone();
+        let catch_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let mut num_comparisons = 0;
+            dmsort::sort_by(&mut data, |a, b| {
+                if num_comparisons == break_after_this_many_comparisons {
+                    panic!("This is a scheduled panic");
+                }
+                num_comparisons += 1;
+                a.cmp(b)
+            });
+        }));
+        assert!(catch_result.is_err());
+
+        data.sort();
+        let mut expected = ids.clone();
+        expected.sort();
+        assert_eq!(data, expected);
+    }
+}
+
+#[test]
+fn test_sort_large() {
+    // 320 bytes, the kind of element `sort_large` is for. We compare it with sorting directly:
+    #[derive(Clone, Debug, PartialEq)]
+    struct Large {
+        key: i32,
+        id: usize,
+        padding: [u64; 39],
+    }
+
+    let lengths = [0, 1, 2, 3, 10, 100, 1000, 10_000];
+    for_each_disorder(&lengths, &[0.0, 0.01, 0.1, 0.3, 0.5, 1.0], |integers| {
+        let data: Vec<Large> = integers
+            .into_iter()
+            .enumerate()
+            .map(|(id, x)| Large {
+                key: x / 4,
+                id,
+                padding: [id as u64; 39],
+            })
+            .collect();
+
+        let mut expected = data.clone();
+        expected.sort_by_key(|x| x.key);
+
+        for sort in &[
+            dmsort::sort_large_by_key::<Large, i32, fn(&Large) -> i32>,
+            dmsort::sort_by_key,
+        ] {
+            let mut sorted = data.clone();
+            sort(&mut sorted, |x| x.key);
+            assert!(sorted
+                .iter()
+                .map(|x| x.key)
+                .eq(expected.iter().map(|x| x.key)));
+            assert!(sorted.iter().all(|x| x.padding == [x.id as u64; 39]));
+            let mut ids: Vec<usize> = sorted.iter().map(|x| x.id).collect();
+            ids.sort();
+            assert!(ids.into_iter().eq(0..data.len()));
+        }
+    });
+
+    // A panic leaves the elements where they were, and they are all dropped once:
+    let ids: Vec<usize> = (0..400)
+        .map(|i| if i % 3 == 0 { 1000 - i } else { i })
+        .collect();
+    for break_after_this_many_comparisons in (0..600).step_by(7) {
+        let dropped = RefCell::new(BTreeSet::new());
+        let catch_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let mut data = test_sort_types(&ids, &dropped);
+            let mut num_comparisons = 0;
+            let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                dmsort::sort_large_by(&mut data, |a, b| {
+                    if num_comparisons == break_after_this_many_comparisons {
+                        panic!("This is a scheduled panic");
+                    }
+                    num_comparisons += 1;
+                    a.id.cmp(&b.id)
+                })
+            }));
+            assert!(data.iter().map(|x| x.id).eq(ids.iter().cloned()));
+            if let Err(err) = result {
+                panic::resume_unwind(err);
+            }
+        }));
+
+        assert!(catch_result.is_err());
+        assert_eq!(dropped.borrow_mut().len(), ids.len());
+    }
+}
+
+#[test]
+fn test_sort_strings() {
+    fn check(strings: Vec<String>) {
+        let mut expected = strings.clone();
+        expected.sort();
+        let mut sorted = strings.clone();
+        dmsort::sort_strings(&mut sorted);
+        assert_eq!(sorted, expected, "FAIL with input {:?}", strings);
+
+        let mut bytes: Vec<Vec<u8>> = strings.iter().map(|s| s.clone().into_bytes()).collect();
+        dmsort::sort_strings(&mut bytes);
+        assert!(bytes
+            .iter()
+            .map(|b| b.as_slice())
+            .eq(expected.iter().map(|s| s.as_bytes())));
+
+        let mut people: Vec<(String, usize)> = strings.into_iter().zip(0..).collect();
+        dmsort::sort_by_str_key(&mut people, |p| p.0.as_str());
+        assert!(people.iter().map(|p| &p.0).eq(expected.iter()));
+    }
+
+    let lengths = [0, 1, 2, 3, 10, 100, 1000];
+    for_each_disorder(&lengths, &[0.0, 0.01, 0.1, 0.5, 1.0], |integers| {
+        // The benchmark strings, where the cached prefix decides:
+        check(integers.iter().map(|x| format!("{:0100}", x)).collect());
+        // Ties between cached prefixes that need the whole string:
+        check(
+            integers
+                .iter()
+                .map(|x| format!("{:03}{:020}", x, x / 3))
+                .collect(),
+        );
+        // Different lengths:
+        check(integers.iter().map(|x| format!("/usr/lib/{}", x)).collect());
+    });
+
+    // Shorter keys that are padded with zeros in the cache:
+    check(
+        [
+            "a",
+            "a\0",
+            "",
+            "a\0\0",
+            "ab",
+            "\0",
+            "a",
+            "a\0\0\0\0\0\0\0\0\0",
+            "a\0\0\0\0\0\0\0\0",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
+    );
+    // The first and last strings share more than all of them:
+    check(
+        [
+            "prefix_1", "prefix_0", "pre", "prefix_2", "pref", "prefix_3",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect(),
+    );
+
+    // A panic in the key function leaves the elements in their original order:
+    let strings: Vec<String> = (0..100).rev().map(|x| format!("{:010}", x)).collect();
+    for break_after_this_many_calls in (0..300).step_by(7) {
+        let mut data = strings.clone();
+        let mut num_calls = 0;
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            dmsort::sort_by_str_key(&mut data, |s| {
+                if num_calls == break_after_this_many_calls {
+                    panic!("This is a scheduled panic");
+                }
+                num_calls += 1;
+                s.as_str()
+            });
+        }));
+        if result.is_err() {
+            assert_eq!(data, strings);
+        } else {
+            assert!(data.iter().rev().eq(strings.iter()));
+        }
+    }
+}